@@ -5,6 +5,7 @@
 
 use seed::prelude::wasm_bindgen;
 use semka_core::prelude::*;
+use semka_core::storage::{HttpStorage, LocalStorageOverlay};
 use semka_widgets::widgets;
 
 // ------ ------
@@ -16,6 +17,7 @@ use semka_widgets::widgets;
 pub fn start() {
     // Mount the `app` to the element with the `id` "app".
     Launcher::new()
+        .storage(LocalStorageOverlay::new(HttpStorage::new()))
         .add_widget(widgets::MarkdownFactory::new())
         .add_widget(widgets::StylesheetFactory::new())
         .root_element("app")
@@ -0,0 +1,510 @@
+use crate::site::Site;
+use crate::storage::FsStorage;
+use failure::{bail, format_err, Error, ResultExt};
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use semka_core::check::check_site;
+use semka_core::constants::{DEFAULT_LANG_ATTR, DEFAULT_TITLE_ATTR, PRERENDERED_ATTR};
+use semka_core::context::{Registry, Routing};
+use semka_core::html;
+use semka_core::manifests::{DocManifest, PageMeta, Redirect, SiteManifest};
+use semka_core::path::Path;
+use semka_core::prerender::Prerenderer;
+use semka_core::utils::is_url_absolute;
+use semka_widgets::widgets;
+use std::fs;
+use std::path::Path as FsPath;
+use std::rc::Rc;
+
+const MARKDOWN_WIDGET: &str = "semka-0.1-markdown";
+const MARKDOWN_FILE: &str = "text.md";
+const APP_DIR: &str = "_app";
+const INDEX_FILE: &str = "index.html";
+
+pub fn init(site: &Site) -> Result<(), Error> {
+    if site.site_manifest_file().exists() {
+        bail!("Site already exists in \"{}\"", site.root().display());
+    }
+    let index_page: Path = "index".parse()?;
+    fs::create_dir_all(site.root())
+        .with_context(|_| format!("Can not create \"{}\"", site.root().display()))?;
+    site.save_site_manifest(&SiteManifest {
+        index_page: index_page.clone(),
+        ..SiteManifest::default()
+    })?;
+    new(site, &index_page, MARKDOWN_WIDGET)?;
+    println!("Site created in \"{}\"", site.root().display());
+    Ok(())
+}
+
+pub fn new(site: &Site, doc_path: &Path, widget: &str) -> Result<(), Error> {
+    if site.has_document(doc_path) {
+        bail!("Document \"{}\" already exists", doc_path);
+    }
+    site.save_doc_manifest(
+        doc_path,
+        &DocManifest {
+            widget: widget.to_string(),
+            ..DocManifest::default()
+        },
+    )?;
+    if widget == MARKDOWN_WIDGET {
+        let text_file = site.doc_dir(doc_path).join(MARKDOWN_FILE);
+        fs::write(&text_file, format!("# {}\n", doc_path))
+            .with_context(|_| format!("Can not write \"{}\"", text_file.display()))?;
+    }
+    println!("Document \"{}\" created", doc_path);
+    Ok(())
+}
+
+pub fn rm(site: &Site, doc_path: &Path) -> Result<(), Error> {
+    if !site.has_document(doc_path) {
+        bail!("Document \"{}\" not found", doc_path);
+    }
+    if let Some(nested) = site
+        .documents()?
+        .into_iter()
+        .find(|nested| nested.is_subpath(doc_path))
+    {
+        bail!("Document \"{}\" contains \"{}\"", doc_path, nested);
+    }
+    let doc_dir = site.doc_dir(doc_path);
+    fs::remove_dir_all(&doc_dir)
+        .with_context(|_| format!("Can not remove \"{}\"", doc_dir.display()))?;
+    println!("Document \"{}\" removed", doc_path);
+    for referrer in references(site, doc_path)? {
+        println!("Warning: \"{}\" still refers to \"{}\"", referrer, doc_path);
+    }
+    Ok(())
+}
+
+pub fn mv(site: &Site, from: &Path, to: &Path, redirect: bool) -> Result<(), Error> {
+    if !site.has_document(from) {
+        bail!("Document \"{}\" not found", from);
+    }
+    if site.doc_dir(to).exists() {
+        bail!("Document \"{}\" already exists", to);
+    }
+    let from_dir = site.doc_dir(from);
+    let to_dir = site.doc_dir(to);
+    if let Some(parent) = to_dir.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|_| format!("Can not create \"{}\"", parent.display()))?;
+    }
+    fs::rename(&from_dir, &to_dir).with_context(|_| {
+        format!(
+            "Can not move \"{}\" to \"{}\"",
+            from_dir.display(),
+            to_dir.display()
+        )
+    })?;
+    println!("Document \"{}\" moved to \"{}\"", from, to);
+
+    let mut site_manifest = site.load_site_manifest()?;
+    let mut manifest_changed = false;
+    for page in site_manifest.pages_mut() {
+        if let Some(renamed) = rename_prefix(page, from, to) {
+            *page = renamed;
+            manifest_changed = true;
+        }
+    }
+    if redirect {
+        site_manifest.redirects.insert(
+            from.clone(),
+            Redirect {
+                to: to.clone(),
+                permanent: true,
+            },
+        );
+        manifest_changed = true;
+    }
+    if manifest_changed {
+        site.save_site_manifest(&site_manifest)?;
+        println!("Updated \"{}\"", site.site_manifest_file().display());
+    }
+
+    for doc_path in site.documents()? {
+        for file in markdown_files(site, &doc_path)? {
+            let text = fs::read_to_string(&file)
+                .with_context(|_| format!("Can not read \"{}\"", file.display()))?;
+            let new_text = replace_links(&text, |target| rename_prefix(target, from, to));
+            if new_text != text {
+                fs::write(&file, new_text)
+                    .with_context(|_| format!("Can not write \"{}\"", file.display()))?;
+                println!("Updated \"{}\"", file.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn ls(site: &Site) -> Result<(), Error> {
+    let site_manifest = site.load_site_manifest()?;
+    let is_page_of =
+        |page: &Path, doc_path: &Path| site.page_document(page).as_ref() == Some(doc_path);
+    for doc_path in site.documents()? {
+        let manifest = site.load_doc_manifest(&doc_path)?;
+        let mut roles = vec![];
+        if is_page_of(&site_manifest.index_page, &doc_path) {
+            roles.push("index");
+        }
+        if site_manifest
+            .master_pages()
+            .iter()
+            .any(|page| is_page_of(page, &doc_path))
+        {
+            roles.push("master");
+        }
+        if is_page_of(&site_manifest.not_found_page, &doc_path) {
+            roles.push("not found");
+        }
+        if roles.is_empty() {
+            println!("{}\t{}", doc_path, manifest.widget);
+        } else {
+            println!("{}\t{}\t({})", doc_path, manifest.widget, roles.join(", "));
+        }
+    }
+    Ok(())
+}
+
+pub fn check(site: &Site) -> Result<(), Error> {
+    let registry = registry();
+    let storage = Rc::new(FsStorage::new(site.root()));
+    let issues = futures::executor::block_on(check_site(&registry, storage))?;
+    for issue in &issues {
+        println!("{}", issue);
+    }
+    if !issues.is_empty() {
+        bail!("Found {} problem(s)", issues.len());
+    }
+    println!("No problems found");
+    Ok(())
+}
+
+pub fn render(
+    site: &Site,
+    out: &FsPath,
+    template: Option<&FsPath>,
+    root_element: &str,
+    base_path: Path,
+    hash_routing: bool,
+) -> Result<(), Error> {
+    let template_file = template
+        .map(FsPath::to_path_buf)
+        .unwrap_or_else(|| site.root().join(APP_DIR).join(INDEX_FILE));
+    let template = fs::read_to_string(&template_file)
+        .with_context(|_| format!("Can not read \"{}\"", template_file.display()))?;
+    let site_manifest = site.load_site_manifest()?;
+    let master_pages: Vec<Path> = site_manifest
+        .master_pages()
+        .iter()
+        .filter_map(|page| site.page_document(page))
+        .collect();
+    let index_page = site_manifest.index_page.clone();
+    let base_path = Path::new_absolute().join(&base_path);
+    let mut prerenderer = Prerenderer::new(
+        site_manifest.clone(),
+        registry(),
+        FsStorage::new(site.root()),
+    )
+    .base_path(base_path.clone());
+
+    // With hash routing the server only ever sees the root path.
+    let mut pages: Vec<Path> = if hash_routing {
+        prerenderer = prerenderer.routing(Routing::Hash);
+        vec![]
+    } else {
+        site.documents()?
+            .into_iter()
+            .filter(|doc_path| !master_pages.contains(doc_path))
+            .filter(|doc_path| site_manifest.redirect(doc_path).is_none())
+            .collect()
+    };
+    pages.insert(0, Path::new());
+    for page_path in pages {
+        let page = futures::executor::block_on(prerenderer.render(&page_path));
+        for notification in &page.notifications {
+            println!(
+                "Warning: {}: {}",
+                display_page(&page_path, &index_page),
+                notification.message
+            );
+        }
+        let html = fill_head(
+            fill_template(&template, root_element, &page.html)?,
+            &base_path,
+            &page.meta,
+            &page.head,
+        );
+        write_page(out, &page_path, &html)?;
+    }
+
+    // Static hosts serve these instead of the app.
+    let redirects = site_manifest.redirects.keys().filter(|_| !hash_routing);
+    for from in redirects {
+        match site_manifest.redirect(from) {
+            Some(redirect) => {
+                let html = redirect_stub(&base_path.join(&redirect.to), redirect.permanent);
+                write_page(out, from, &html)?;
+            }
+            None => println!("Warning: Redirect loop from \"{}\"", from),
+        }
+    }
+    Ok(())
+}
+
+fn write_page(out: &FsPath, page_path: &Path, html: &str) -> Result<(), Error> {
+    let file = page_path
+        .iter()
+        .fold(out.to_path_buf(), |dir, part| dir.join(part))
+        .join(INDEX_FILE);
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|_| format!("Can not create \"{}\"", dir.display()))?;
+    }
+    fs::write(&file, html).with_context(|_| format!("Can not write \"{}\"", file.display()))?;
+    println!("Rendered \"{}\"", file.display());
+    Ok(())
+}
+
+/// Page that sends browsers and crawlers to `target`.
+fn redirect_stub(target: &Path, permanent: bool) -> String {
+    let target = html::escape(&target.to_string(), true);
+    let index = if permanent {
+        format!("<link rel=\"canonical\" href=\"{}\" />", target)
+    } else {
+        "<meta name=\"robots\" content=\"noindex\" />".to_string()
+    };
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+  <head>
+    <meta charset="utf-8" />
+    <title>Redirecting</title>
+    <meta http-equiv="refresh" content="0; url={target}" />
+    {index}
+  </head>
+  <body>
+    <a href="{target}">{target}</a>
+  </body>
+</html>
+"#,
+        target = target,
+        index = index
+    )
+}
+
+fn registry() -> Registry {
+    let registry = Registry::builtin()
+        .add_widget(widgets::MarkdownFactory::new())
+        .add_widget(widgets::StylesheetFactory::new());
+    for warning in registry.warnings() {
+        println!("Warning: {}", warning);
+    }
+    registry
+}
+
+fn display_page(page_path: &Path, index_page: &Path) -> String {
+    if page_path.is_empty() {
+        index_page.to_string()
+    } else {
+        page_path.to_string()
+    }
+}
+
+/// Replaces content of the element with `id` attribute equal to `root_element`
+/// with the pre-rendered `html` and marks the element as pre-rendered.
+fn fill_template(template: &str, root_element: &str, html: &str) -> Result<String, Error> {
+    let id_attr = format!("id=\"{}\"", root_element);
+    let not_found = || format_err!("Element with {} not found in template", id_attr);
+    let id_pos = template.find(&id_attr).ok_or_else(not_found)?;
+    let open_start = template[..id_pos].rfind('<').ok_or_else(not_found)?;
+    let open_end = id_pos + template[id_pos..].find('>').ok_or_else(not_found)?;
+    let tag: String = template[open_start + 1..]
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect();
+    let close_tag = format!("</{}>", tag);
+    let close_start = open_end
+        + template[open_end..]
+            .find(&close_tag)
+            .ok_or_else(not_found)?;
+    let open_tag = template[open_start..open_end].trim_end_matches('/');
+    Ok(format!(
+        "{} {}>{}{}",
+        &template[..open_start + open_tag.len()],
+        PRERENDERED_ATTR,
+        html,
+        &template[close_start..]
+    ))
+}
+
+/// Puts page metadata and head elements of widgets into the template head
+/// the same way browse mode does. `<base>` points to `base_path`,
+/// so pages in subdirectories load the app from the site root.
+fn fill_head(template: String, base_path: &Path, meta: &PageMeta, elements: &[String]) -> String {
+    lazy_static! {
+        static ref BASE_RE: Regex = Regex::new(r"(?i)<base\b[^>]*>\s*").unwrap();
+        static ref HEAD_RE: Regex = Regex::new(r"(?i)<head\b[^>]*>").unwrap();
+        static ref TITLE_RE: Regex = Regex::new(r"(?is)<title>(.*?)</title>").unwrap();
+        static ref HTML_RE: Regex = Regex::new(r"(?i)<html\b([^>]*)>").unwrap();
+        static ref LANG_RE: Regex = Regex::new(r#"(?i)\slang="([^"]*)""#).unwrap();
+        static ref META_RE: Regex = Regex::new(r"(?i)<meta\s[^>]*>\s*").unwrap();
+        static ref META_KEY_RE: Regex = Regex::new(r#"(?i)\s(name|property)="([^"]*)""#).unwrap();
+    }
+    let base = format!(
+        "<base href=\"{}/\" />",
+        html::escape(base_path.to_string().trim_end_matches('/'), true)
+    );
+    let mut html = BASE_RE.replace_all(&template, "").to_string();
+    html = HEAD_RE
+        .replace(&html, |caps: &Captures| {
+            format!("{}\n    {}", &caps[0], base)
+        })
+        .to_string();
+    let mut head_tags = String::new();
+    // Template title and language are kept for pages without own ones in browse mode.
+    if let Some(title) = &meta.title {
+        let title = |default: &str| {
+            format!(
+                "<title {}=\"{}\">{}</title>",
+                DEFAULT_TITLE_ATTR,
+                default.replace('"', "&quot;"),
+                html::escape(title, false)
+            )
+        };
+        if TITLE_RE.is_match(&html) {
+            html = TITLE_RE
+                .replace(&html, |caps: &Captures| title(&caps[1]))
+                .to_string();
+        } else {
+            head_tags.push_str(&format!("{}\n  ", title("")));
+        }
+    }
+    if let Some(lang) = &meta.lang {
+        html = HTML_RE
+            .replace(&html, |caps: &Captures| {
+                let default = LANG_RE
+                    .captures(&caps[1])
+                    .map_or(String::new(), |lang| lang[1].to_string());
+                format!(
+                    "<html lang=\"{}\" {}=\"{}\"{}>",
+                    html::escape(lang, true),
+                    DEFAULT_LANG_ATTR,
+                    default,
+                    LANG_RE.replace(&caps[1], "")
+                )
+            })
+            .to_string();
+    }
+    // Template tags for keys of the page metadata are replaced.
+    let meta_tags = meta.meta_tags();
+    html = META_RE
+        .replace_all(&html, |caps: &Captures| {
+            let replaced = META_KEY_RE.captures(&caps[0]).map_or(false, |key| {
+                meta_tags.iter().any(|(key_attr, name, _)| {
+                    key[1].eq_ignore_ascii_case(key_attr) && &key[2] == *name
+                })
+            });
+            if replaced {
+                String::new()
+            } else {
+                caps[0].to_string()
+            }
+        })
+        .to_string();
+    for (key_attr, key, content) in meta_tags {
+        if let Some(content) = content {
+            head_tags.push_str(&format!(
+                "<meta {}=\"{}\" content=\"{}\" />\n  ",
+                key_attr,
+                key,
+                html::escape(&content, true)
+            ));
+        }
+    }
+    for element in elements {
+        head_tags.push_str(&format!("{}\n  ", element));
+    }
+    match html.find("</head>") {
+        Some(pos) => html.insert_str(pos, &head_tags),
+        None => html.insert_str(0, &head_tags),
+    }
+    html
+}
+
+/// Documents and site manifest entries that refer to `doc_path`.
+fn references(site: &Site, doc_path: &Path) -> Result<Vec<String>, Error> {
+    let mut referrers = vec![];
+    let site_manifest = site.load_site_manifest()?;
+    if site_manifest
+        .pages()
+        .iter()
+        .any(|page| rename_prefix(page, doc_path, doc_path).is_some())
+    {
+        referrers.push(site.site_manifest_file().display().to_string());
+    }
+    for referrer in site.documents()? {
+        for file in markdown_files(site, &referrer)? {
+            let text = fs::read_to_string(&file)
+                .with_context(|_| format!("Can not read \"{}\"", file.display()))?;
+            let mut found = false;
+            replace_links(&text, |target| {
+                found |= rename_prefix(target, doc_path, doc_path).is_some();
+                None
+            });
+            if found {
+                referrers.push(file.display().to_string());
+            }
+        }
+    }
+    Ok(referrers)
+}
+
+fn markdown_files(site: &Site, doc_path: &Path) -> Result<Vec<std::path::PathBuf>, Error> {
+    let doc_dir = site.doc_dir(doc_path);
+    Ok(site
+        .doc_files(doc_path)?
+        .into_iter()
+        .filter(|file| file.to_string().ends_with(".md"))
+        .map(|file| {
+            file.iter()
+                .fold(doc_dir.clone(), |dir, part| dir.join(part))
+        })
+        .collect())
+}
+
+/// Replaces `from` prefix of the path with `to`.
+/// Returns `None` if the path is not `from` or does not start with it.
+fn rename_prefix(path: &Path, from: &Path, to: &Path) -> Option<Path> {
+    if path == from || path.is_subpath(from) {
+        Some(to.join(&path.clone().releative_to(from).ok()?))
+    } else {
+        None
+    }
+}
+
+/// Calls `rename` for every relative link and include target in Markdown text
+/// and replaces the target if `rename` returns new path.
+fn replace_links<F>(text: &str, mut rename: F) -> String
+where
+    F: FnMut(&Path) -> Option<Path>,
+{
+    lazy_static! {
+        static ref LINK_RE: Regex = Regex::new(r"\]\(([^)\s]+)\)").unwrap();
+    }
+    LINK_RE
+        .replace_all(text, |caps: &Captures| {
+            let target = &caps[1];
+            let renamed = Some(target)
+                .filter(|target| !is_url_absolute(target))
+                .and_then(|target| target.parse::<Path>().ok())
+                .and_then(|path| rename(&path));
+            match renamed {
+                Some(path) if target.ends_with('/') => format!("]({}/)", path),
+                Some(path) => format!("]({})", path),
+                None => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
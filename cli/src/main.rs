@@ -0,0 +1,101 @@
+use failure::Error;
+use semka_core::path::Path;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+mod commands;
+mod site;
+mod storage;
+
+use site::Site;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "semka", about = "Manage Semka sites")]
+struct Opt {
+    /// Site directory
+    #[structopt(short, long, default_value = ".", parse(from_os_str))]
+    site: PathBuf,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Create new site with index page
+    Init,
+    /// Create new document
+    New {
+        doc: Path,
+        /// Widget of the document
+        #[structopt(short, long, default_value = "semka-0.1-markdown")]
+        widget: String,
+    },
+    /// Remove document
+    Rm { doc: Path },
+    /// Rename document and update references to it
+    Mv {
+        from: Path,
+        to: Path,
+        /// Redirect the old path to the new one
+        #[structopt(long)]
+        redirect: bool,
+    },
+    /// List documents
+    Ls,
+    /// Check manifests, widgets and dependencies of documents
+    Check,
+    /// Pre-render pages to HTML
+    Render {
+        /// Output directory
+        #[structopt(short, long, default_value = "dist", parse(from_os_str))]
+        out: PathBuf,
+        /// Page template, `_app/index.html` of the site by default
+        #[structopt(short, long, parse(from_os_str))]
+        template: Option<PathBuf>,
+        /// Id of the element pages are rendered into
+        #[structopt(short, long, default_value = "app")]
+        root_element: String,
+        /// Absolute path the site is served from
+        #[structopt(short, long, default_value = "/")]
+        base_path: Path,
+        /// Link pages as `#/page` like the app with hash routing, only the index page is rendered
+        #[structopt(long)]
+        hash_routing: bool,
+    },
+}
+
+fn run(opt: Opt) -> Result<(), Error> {
+    let site = Site::new(opt.site);
+    match opt.command {
+        Command::Init => commands::init(&site),
+        Command::New { doc, widget } => commands::new(&site, &doc, &widget),
+        Command::Rm { doc } => commands::rm(&site, &doc),
+        Command::Mv { from, to, redirect } => commands::mv(&site, &from, &to, redirect),
+        Command::Ls => commands::ls(&site),
+        Command::Check => commands::check(&site),
+        Command::Render {
+            out,
+            template,
+            root_element,
+            base_path,
+            hash_routing,
+        } => commands::render(
+            &site,
+            &out,
+            template.as_deref(),
+            &root_element,
+            base_path,
+            hash_routing,
+        ),
+    }
+}
+
+fn main() {
+    if let Err(err) = run(Opt::from_args()) {
+        eprintln!("Error: {}", err);
+        for cause in err.iter_causes() {
+            eprintln!("  Caused by: {}", cause);
+        }
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,155 @@
+use failure::{format_err, Error, ResultExt};
+use semka_core::constants::{DOC_DIR, DOC_MANIFEST_FILE, SITE_MANIFEST_FILE};
+use semka_core::manifests::{DocManifest, SiteManifest};
+use semka_core::path::Path;
+use semka_core::utils;
+use std::fs;
+use std::path::{Path as FsPath, PathBuf};
+
+/// Site directory on the local file system.
+#[derive(Debug)]
+pub struct Site {
+    root: PathBuf,
+}
+
+impl Site {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &FsPath {
+        &self.root
+    }
+
+    pub fn site_manifest_file(&self) -> PathBuf {
+        self.root.join(SITE_MANIFEST_FILE)
+    }
+
+    pub fn load_site_manifest(&self) -> Result<SiteManifest, Error> {
+        read_json(&self.site_manifest_file()).map(SiteManifest::normalized)
+    }
+
+    pub fn save_site_manifest(&self, manifest: &SiteManifest) -> Result<(), Error> {
+        write_json(&self.site_manifest_file(), manifest)
+    }
+
+    /// File system path of the document's own directory.
+    pub fn doc_dir(&self, doc_path: &Path) -> PathBuf {
+        doc_path
+            .iter()
+            .fold(self.root.join(DOC_DIR), |dir, part| dir.join(part))
+    }
+
+    pub fn doc_manifest_file(&self, doc_path: &Path) -> PathBuf {
+        self.doc_dir(doc_path).join(DOC_MANIFEST_FILE)
+    }
+
+    pub fn has_document(&self, doc_path: &Path) -> bool {
+        self.doc_manifest_file(doc_path).is_file()
+    }
+
+    pub fn load_doc_manifest(&self, doc_path: &Path) -> Result<DocManifest, Error> {
+        read_json(&self.doc_manifest_file(doc_path))
+    }
+
+    pub fn save_doc_manifest(&self, doc_path: &Path, manifest: &DocManifest) -> Result<(), Error> {
+        let doc_dir = self.doc_dir(doc_path);
+        fs::create_dir_all(&doc_dir)
+            .with_context(|_| format!("Can not create \"{}\"", doc_dir.display()))?;
+        write_json(&self.doc_manifest_file(doc_path), manifest)
+    }
+
+    /// Document the page belongs to, the longest prefix of the page path
+    /// having a manifest.
+    pub fn page_document(&self, page_path: &Path) -> Option<Path> {
+        utils::split_doc_path(page_path, |doc_path| self.has_document(doc_path))
+            .map(|(doc_path, _)| doc_path)
+    }
+
+    /// All documents of the site, nested ones included, in alphabetical order.
+    pub fn documents(&self) -> Result<Vec<Path>, Error> {
+        fn walk(
+            site: &Site,
+            dir: &FsPath,
+            prefix: &Path,
+            documents: &mut Vec<Path>,
+        ) -> Result<(), Error> {
+            for entry in read_dir(dir)? {
+                if !entry.path().is_dir() {
+                    continue;
+                }
+                let name = entry.file_name();
+                let name = name.to_str().ok_or_else(|| {
+                    format_err!("Bad document name \"{}\"", name.to_string_lossy())
+                })?;
+                let doc_path = prefix.add(name);
+                if site.has_document(&doc_path) {
+                    documents.push(doc_path.clone());
+                }
+                walk(site, &entry.path(), &doc_path, documents)?;
+            }
+            Ok(())
+        }
+        let doc_root = self.root.join(DOC_DIR);
+        if !doc_root.is_dir() {
+            return Ok(vec![]);
+        }
+        let mut documents = vec![];
+        walk(self, &doc_root, &Path::new(), &mut documents)?;
+        documents.sort();
+        Ok(documents)
+    }
+
+    /// Files of the document relative to its directory, except the manifest
+    /// and files of nested documents.
+    pub fn doc_files(&self, doc_path: &Path) -> Result<Vec<Path>, Error> {
+        fn walk(dir: &FsPath, prefix: &Path, files: &mut Vec<Path>) -> Result<(), Error> {
+            for entry in read_dir(dir)? {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let path = prefix.add(&name);
+                if entry.path().join(DOC_MANIFEST_FILE).is_file() {
+                    continue;
+                } else if entry.path().is_dir() {
+                    walk(&entry.path(), &path, files)?;
+                } else {
+                    files.push(path);
+                }
+            }
+            Ok(())
+        }
+        let mut files = vec![];
+        walk(&self.doc_dir(doc_path), &Path::new(), &mut files)?;
+        files.retain(|file| file.to_string() != DOC_MANIFEST_FILE);
+        files.sort();
+        Ok(files)
+    }
+}
+
+fn read_dir(dir: &FsPath) -> Result<Vec<fs::DirEntry>, Error> {
+    let entries = fs::read_dir(dir)
+        .with_context(|_| format!("Can not read \"{}\"", dir.display()))?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|_| format!("Can not read \"{}\"", dir.display()))?;
+    Ok(entries)
+}
+
+pub fn read_json<T>(file: &FsPath) -> Result<T, Error>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let text = fs::read_to_string(file)
+        .with_context(|_| format!("Can not read \"{}\"", file.display()))?;
+    let value = serde_json::from_str(&text)
+        .with_context(|_| format!("Can not parse \"{}\"", file.display()))?;
+    Ok(value)
+}
+
+pub fn write_json<T>(file: &FsPath, value: &T) -> Result<(), Error>
+where
+    T: serde::Serialize,
+{
+    let mut text = serde_json::to_string_pretty(value)?;
+    text.push('\n');
+    fs::write(file, text).with_context(|_| format!("Can not write \"{}\"", file.display()))?;
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use bytes::Bytes;
+use futures::future::{self, FutureExt, LocalBoxFuture};
+use semka_core::error::FetchError;
+use semka_core::path::Path;
+use semka_core::storage::Storage;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+/// Storage that reads site files from the local file system.
+#[derive(Debug)]
+pub struct FsStorage {
+    root: PathBuf,
+}
+
+impl FsStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn file(&self, path: &Path) -> PathBuf {
+        path.iter()
+            .fold(self.root.clone(), |dir, part| dir.join(part))
+    }
+}
+
+impl Storage for FsStorage {
+    fn read(&self, path: &Path) -> LocalBoxFuture<'static, Result<Bytes, FetchError>> {
+        let result = fs::read(self.file(path))
+            .map(Bytes::from)
+            .map_err(|err| to_fetch_error(path, err));
+        future::ready(result).boxed_local()
+    }
+
+    fn can_list(&self) -> bool {
+        true
+    }
+    fn list(&self, path: &Path) -> LocalBoxFuture<'static, Result<Vec<Path>, FetchError>> {
+        fn walk(dir: PathBuf, prefix: Path, files: &mut Vec<Path>) -> std::io::Result<()> {
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                let path = prefix.add(entry.file_name().to_string_lossy());
+                if entry.file_type()?.is_dir() {
+                    walk(entry.path(), path, files)?;
+                } else {
+                    files.push(path);
+                }
+            }
+            Ok(())
+        }
+        let mut files = vec![];
+        let result = walk(self.file(path), Path::new(), &mut files)
+            .map(|_| files)
+            .map_err(|err| to_fetch_error(path, err));
+        future::ready(result).boxed_local()
+    }
+}
+
+fn to_fetch_error(path: &Path, err: std::io::Error) -> FetchError {
+    if err.kind() == ErrorKind::NotFound {
+        FetchError::ClientError {
+            url: path.to_string(),
+            code: 404,
+            text: err.to_string(),
+        }
+    } else {
+        FetchError::StorageError(path.to_string(), err.to_string())
+    }
+}
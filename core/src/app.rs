@@ -1,15 +1,22 @@
-use crate::context::{Context, Registry};
+use crate::constants::{DEFAULT_CACHE_SIZE, PRERENDERED_ATTR, SITE_MANIFEST_FILE};
+use crate::context::{Context, Registry, Routing};
+use crate::head;
 use crate::manifests::SiteManifest;
-use crate::node_ext::{to_absolute_href, NodeExt};
+use crate::node_ext::resolve_urls;
+use crate::notification::Notification;
 use crate::path::Path;
+use crate::storage::{HttpStorage, RetryPolicy, RetryStorage, Storage};
 use crate::utils;
 use crate::widget::WidgetFactory;
 use failure::{format_err, Error};
 use seed::{prelude::*, *};
+use std::collections::BTreeMap;
+use std::rc::Rc;
 
 mod about;
 pub mod browse;
 pub mod edit;
+mod notifications;
 
 // ------ ------
 //   Launcher
@@ -18,15 +25,23 @@ pub mod edit;
 pub struct Launcher {
     registry: Registry,
     root_element: Option<String>,
+    storage: Option<Rc<dyn Storage>>,
+    retry_policy: RetryPolicy,
+    cache_size: usize,
+    routing: Routing,
+    site_root: SiteRoot,
 }
 
 impl Launcher {
     pub fn new() -> Self {
-        use crate::builtin_widgets;
-        let registry = Registry::new().add_widget(builtin_widgets::LoadingFactory::new());
         Self {
-            registry,
+            registry: Registry::builtin(),
             root_element: None,
+            storage: None,
+            retry_policy: RetryPolicy::default(),
+            cache_size: DEFAULT_CACHE_SIZE,
+            routing: Routing::default(),
+            site_root: SiteRoot::Discover,
         }
     }
 
@@ -47,12 +62,63 @@ impl Launcher {
         }
     }
 
+    pub fn storage<S>(self, storage: S) -> Self
+    where
+        S: Storage + 'static,
+    {
+        Self {
+            storage: Some(Rc::new(storage)),
+            ..self
+        }
+    }
+
+    pub fn retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy,
+            ..self
+        }
+    }
+
+    /// How many documents no longer used by the current page stay loaded,
+    /// so going back to recently visited pages doesn't load them again.
+    pub fn cache_size(self, cache_size: usize) -> Self {
+        Self { cache_size, ..self }
+    }
+
+    /// Keeps page paths in the URL hash, so the site works on hosts
+    /// that can not serve the app for every path.
+    pub fn routing(self, routing: Routing) -> Self {
+        Self { routing, ..self }
+    }
+
+    /// Absolute path the site is served from.
+    /// If neither it nor the site manifest URL is set, the path is taken from
+    /// `<base>` of the page. Pages without `<base>` look for the site manifest
+    /// in their directory and up to the server root.
+    pub fn base_path(self, base_path: Path) -> Self {
+        Self {
+            site_root: SiteRoot::BasePath(Path::new_absolute().join(&base_path)),
+            ..self
+        }
+    }
+
+    /// URL of the site manifest, the site is served from its directory.
+    pub fn site_manifest_url(self, url: impl AsRef<str>) -> Self {
+        Self {
+            site_root: SiteRoot::SiteManifestUrl(url.as_ref().to_string()),
+            ..self
+        }
+    }
+
     pub fn start(mut self) {
         let root_element = self.root_element.take().unwrap_or("app".to_string());
-        let registry = self.registry;
+        let prerendered = seed::document()
+            .get_element_by_id(&root_element)
+            .filter(|el| el.has_attribute(PRERENDERED_ATTR))
+            .map(|el| Node::from_html(&el.inner_html()));
         seed::App::start(
             root_element.as_str(),
-            |url, orders| init(registry, url, orders),
+            move |url, orders| init(self, prerendered, url, orders),
             update,
             view,
         );
@@ -64,7 +130,12 @@ impl Launcher {
 // ------ ------
 
 // `init` describes what should happen when your app started.
-fn init(registry: Registry, url: Url, orders: &mut impl Orders<Msg>) -> Model {
+fn init(
+    launcher: Launcher,
+    prerendered: Option<Vec<Node<Msg>>>,
+    url: Url,
+    orders: &mut impl Orders<Msg>,
+) -> Model {
     // When the `console_error_panic_hook` feature is enabled, we can call the
     // `set_panic_hook` function at least once during initialization, and then
     // we will get better error messages if our code ever panics.
@@ -74,27 +145,38 @@ fn init(registry: Registry, url: Url, orders: &mut impl Orders<Msg>) -> Model {
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
 
+    let storage = launcher
+        .storage
+        .unwrap_or_else(|| Rc::new(HttpStorage::new()));
+    let storage: Rc<dyn Storage> = Rc::new(RetryStorage::new(storage, launcher.retry_policy));
+
     orders
-        .perform_cmd(utils::fetch_site_manifest().map_ok_or_else(
-            |err| Msg::ShowError(err.into()),
-            |manifest| Msg::SiteManifestChanged(fix_site_manifest(manifest)),
+        .perform_cmd(find_base_path(launcher.site_root).map_ok_or_else(
+            |err| Msg::Notify(Notification::error(err)),
+            Msg::BasePathFound,
         ))
-        .subscribe(|url_changed: subs::UrlChanged| Msg::UrlChanged(url_changed.0));
+        .subscribe(|url_changed: subs::UrlChanged| Msg::UrlChanged(url_changed.0))
+        .subscribe(Msg::Notify);
+
+    // The page path is found again once the site manifest is loaded.
+    let base_path = Path::new_absolute();
+    let page_path = url_to_page_path(&url, &base_path, launcher.routing);
+
+    for warning in launcher.registry.warnings() {
+        orders.notify(Notification::warning(warning));
+    }
 
-    let base_path = Path::new_absolute().join(
-        &orders
-            .clone_base_path()
-            .iter()
-            .filter(|p| !p.is_empty())
-            .collect::<Path>(),
-    );
-    let page_path = url_to_page_path(&url, &base_path);
     let ctx = Context {
+        query: url_to_query(&url, launcher.routing),
+        fragment: url_to_fragment(&url, launcher.routing),
         url,
         page_path,
         base_path,
+        routing: launcher.routing,
         site_manifest: SiteManifest::default(),
-        registry,
+        registry: launcher.registry,
+        storage,
+        cache_size: launcher.cache_size,
     };
 
     Model {
@@ -102,6 +184,8 @@ fn init(registry: Registry, url: Url, orders: &mut impl Orders<Msg>) -> Model {
         mode: Mode::Loading,
         browse: None,
         edit: None,
+        notifications: notifications::Model::default(),
+        prerendered,
     }
 }
 
@@ -117,6 +201,10 @@ struct Model {
     mode: Mode,
     browse: Option<browse::Model>,
     edit: Option<edit::Model>,
+    notifications: notifications::Model,
+    // Content of the root element rendered by `semka render`.
+    // Shown until the page is loaded to avoid flashing "Loading...".
+    prerendered: Option<Vec<Node<Msg>>>,
 }
 
 #[derive(Debug)]
@@ -137,8 +225,10 @@ enum Msg {
     BrowseMsg(browse::Msg),
     EditMsg(edit::Msg),
     UrlChanged(Url),
+    BasePathFound(Path),
     SiteManifestChanged(SiteManifest),
-    ShowError(Error),
+    NotificationsMsg(notifications::Msg),
+    Notify(Notification),
 }
 
 // `update` describes how to handle each `Msg`.
@@ -146,6 +236,18 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
     #[cfg(debug_assertions)]
     log!("app::update", msg);
     match msg {
+        Msg::BasePathFound(base_path) => {
+            if let Err(err) = head::set_base(&base_path) {
+                error!("Can not set document base", err);
+            }
+            model.ctx.base_path = base_path;
+            orders.perform_cmd(
+                utils::fetch_site_manifest(&*model.ctx.storage).map_ok_or_else(
+                    |err| Msg::Notify(Notification::error(err)),
+                    |manifest| Msg::SiteManifestChanged(manifest.normalized()),
+                ),
+            );
+        }
         Msg::SiteManifestChanged(site_manifest) => {
             model.ctx.site_manifest = site_manifest;
             let site_manifest = &model.ctx.site_manifest;
@@ -168,10 +270,23 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
             orders.notify(subs::UrlChanged(model.ctx.url.clone()));
         }
         Msg::UrlChanged(url) => {
-            let page_path = url_to_page_path(&url, &model.ctx.base_path);
+            let page_path = url_to_page_path(&url, &model.ctx.base_path, model.ctx.routing);
+            if let Some(redirect) = model.ctx.site_manifest.redirect(&page_path) {
+                let url = set_page_path(url, &redirect.to, &model.ctx);
+                url.go_and_replace();
+                orders.skip().notify(subs::UrlChanged(url));
+                return;
+            }
             let mode = path_to_mode(&page_path);
+            let query = url_to_query(&url, model.ctx.routing);
+            let fragment = url_to_fragment(&url, model.ctx.routing);
+            let page_changed = page_path != model.ctx.page_path || model.browse.is_none();
+            let query_changed = query != model.ctx.query;
+            let fragment_changed = fragment != model.ctx.fragment;
 
             model.ctx.page_path = page_path;
+            model.ctx.query = query;
+            model.ctx.fragment = fragment;
             model.ctx.url = url.clone();
 
             match mode {
@@ -200,6 +315,23 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
                         &mut browse_orders,
                         &model.ctx,
                     );
+                    // Documents stay as they are, widgets read the new query themselves.
+                    if query_changed && !page_changed {
+                        browse::update(
+                            browse::Msg::QueryChanged,
+                            &mut browse_model,
+                            &mut browse_orders,
+                            &model.ctx,
+                        );
+                    }
+                    if model.ctx.fragment.is_some() && (page_changed || fragment_changed) {
+                        browse::update(
+                            browse::Msg::ScrollToFragment,
+                            &mut browse_model,
+                            &mut browse_orders,
+                            &model.ctx,
+                        );
+                    }
                     model.browse.replace(browse_model);
                 }
                 Mode::Loading | Mode::About => (),
@@ -215,7 +347,7 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
                     &model.ctx,
                 );
             } else {
-                orders.send_msg(Msg::ShowError(format_err!("Browse mode not initialized")));
+                orders.notify(Notification::error("Browse mode not initialized"));
             }
         }
         Msg::EditMsg(edit_msg) => {
@@ -227,15 +359,36 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
                     &model.ctx,
                 );
             } else {
-                orders.send_msg(Msg::ShowError(format_err!("Edit mode not initialized")));
+                orders.notify(Notification::error("Edit mode not initialized"));
             }
         }
-        Msg::ShowError(err) => error!(err),
+        Msg::NotificationsMsg(notifications_msg) => notifications::update(
+            notifications_msg,
+            &mut model.notifications,
+            &mut orders.proxy(Msg::NotificationsMsg),
+        ),
+        Msg::Notify(notification) => notifications::update(
+            notifications::Msg::Show(notification),
+            &mut model.notifications,
+            &mut orders.proxy(Msg::NotificationsMsg),
+        ),
+    }
+
+    if model.prerendered.is_some() && is_page_loaded(model) {
+        model.prerendered = None;
+    }
+}
+
+fn is_page_loaded(model: &Model) -> bool {
+    match model.mode {
+        Mode::Browse => model.browse.as_ref().map_or(false, browse::is_loaded),
+        Mode::Loading => false,
+        Mode::Edit | Mode::About => true,
     }
 }
 
 fn path_to_mode(page_path: &Path) -> Mode {
-    let first_part = page_path.iter().nth(0).unwrap_or("");
+    let first_part = page_path.iter().next().unwrap_or("");
     match first_part {
         "_edit" => Mode::Edit,
         "_app" => Mode::About,
@@ -243,9 +396,144 @@ fn path_to_mode(page_path: &Path) -> Mode {
     }
 }
 
-fn url_to_page_path(url: &Url, base_path: &Path) -> Path {
-    let abs_path: Path = Path::new_absolute().join(&url.path().iter().collect::<Path>());
-    abs_path.releative_to(base_path).unwrap()
+fn url_to_page_path(url: &Url, base_path: &Path, routing: Routing) -> Path {
+    match routing {
+        Routing::Path => {
+            let abs_path: Path = Path::new_absolute().join(&url.path().iter().collect::<Path>());
+            abs_path.releative_to(base_path).unwrap()
+        }
+        Routing::Hash => url
+            .hash()
+            .map(|hash| {
+                split_hash(hash)
+                    .0
+                    .split('/')
+                    .filter(|part| !part.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+fn url_to_query(url: &Url, routing: Routing) -> BTreeMap<String, Vec<String>> {
+    match routing {
+        Routing::Path => url
+            .search()
+            .iter()
+            .map(|(key, values)| (key.clone(), values.clone()))
+            .collect(),
+        // Seed decodes the whole hash, so encoded `&` and `=` are lost there.
+        // The query is parsed from the raw hash and decoded like `Url::search`.
+        Routing::Hash => raw_hash(url)
+            .as_deref()
+            .and_then(|hash| split_hash(hash).1)
+            .and_then(|query| web_sys::UrlSearchParams::new_with_str(query).ok())
+            .map(UrlSearch::from)
+            .map(|search| {
+                search
+                    .iter()
+                    .map(|(key, values)| (key.clone(), values.clone()))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+// Undecoded hash of `url` without `#`, taken from the current location if it is
+// the same URL.
+fn raw_hash(url: &Url) -> Option<String> {
+    let hash = window().location().hash().ok()?;
+    let hash = hash.trim_start_matches('#');
+    match Url::decode_uri_component(hash) {
+        Ok(decoded) if Some(&decoded) == url.hash() => Some(hash.to_string()),
+        _ => url.hash().cloned(),
+    }
+}
+
+fn url_to_fragment(url: &Url, routing: Routing) -> Option<String> {
+    let fragment = match routing {
+        Routing::Path => url.hash().map(String::as_str),
+        Routing::Hash => url.hash().and_then(|hash| split_hash(hash).2),
+    };
+    fragment
+        .filter(|fragment| !fragment.is_empty())
+        .map(str::to_string)
+}
+
+// Splits the hash of hash routing URLs, `/page/path?query#fragment`.
+fn split_hash(hash: &str) -> (&str, Option<&str>, Option<&str>) {
+    let (rest, fragment) = match hash.find('#') {
+        Some(pos) => (&hash[..pos], Some(&hash[pos + 1..])),
+        None => (hash, None),
+    };
+    match rest.find('?') {
+        Some(pos) => (&rest[..pos], Some(&rest[pos + 1..]), fragment),
+        None => (rest, None, fragment),
+    }
+}
+
+fn set_page_path(url: Url, page_path: &Path, ctx: &Context) -> Url {
+    match ctx.routing {
+        Routing::Path => url.set_path(ctx.base_path.join(page_path).iter()),
+        Routing::Hash => url.set_hash(format!("/{}", page_path)),
+    }
+}
+
+// ------ ------
+//   Base path
+// ------ ------
+
+// Where the site is served from.
+enum SiteRoot {
+    BasePath(Path),
+    SiteManifestUrl(String),
+    Discover,
+}
+
+async fn find_base_path(site_root: SiteRoot) -> Result<Path, Error> {
+    match site_root {
+        SiteRoot::BasePath(base_path) => Ok(base_path),
+        SiteRoot::SiteManifestUrl(url) => url_dir(&url),
+        SiteRoot::Discover => {
+            let has_base = seed::document()
+                .query_selector("base")
+                .ok()
+                .flatten()
+                .is_some();
+            if has_base {
+                url_dir("")
+            } else {
+                discover_base_path().await
+            }
+        }
+    }
+}
+
+// Looks for the site manifest in the page directory and up to the server root.
+async fn discover_base_path() -> Result<Path, Error> {
+    let mut dir = url_dir("")?;
+    loop {
+        let url = dir.add(SITE_MANIFEST_FILE);
+        if utils::fetch_json::<_, SiteManifest>(url).await.is_ok() {
+            return Ok(dir);
+        }
+        if dir.is_empty() {
+            return Err(format_err!("Can not find {}", SITE_MANIFEST_FILE));
+        }
+        dir = Path::new_absolute().join(&dir.iter().take(dir.len() - 1).collect());
+    }
+}
+
+// Absolute path of the directory `url` points into.
+// Relative URLs are resolved against the document base.
+fn url_dir(url: &str) -> Result<Path, Error> {
+    let js_err = |err: JsValue| format_err!("Invalid URL \"{}\": {:?}", url, err);
+    let base = seed::document().base_uri().map_err(js_err)?;
+    let pathname = web_sys::Url::new_with_base(url, &base.unwrap_or_default())
+        .map_err(js_err)?
+        .pathname();
+    let dir = Path::from_url_path(&pathname[..pathname.rfind('/').unwrap_or(0)])?;
+    Ok(Path::new_absolute().join(&dir))
 }
 
 // ------ ------
@@ -255,7 +543,26 @@ fn url_to_page_path(url: &Url, base_path: &Path) -> Path {
 // (Remove the line below once your `Model` become more complex.)
 #[allow(clippy::trivially_copy_pass_by_ref)]
 // `view` describes what to display.
-fn view(model: &Model) -> Node<Msg> {
+fn view(model: &Model) -> Vec<Node<Msg>> {
+    let notifications = resolve_urls(
+        notifications::view(&model.notifications).map_msg(Msg::NotificationsMsg),
+        &Path::new(),
+        &model.ctx,
+    );
+    // Errors of loading the site are shown over the pre-rendered page too.
+    if let Some(prerendered) = &model.prerendered {
+        return std::iter::once(notifications)
+            .chain(prerendered.iter().cloned())
+            .collect();
+    }
+    // Views of documents resolve their own links.
+    vec![
+        notifications,
+        resolve_urls(view_mode(model), &Path::new(), &model.ctx),
+    ]
+}
+
+fn view_mode(model: &Model) -> Node<Msg> {
     match model.mode {
         Mode::Browse => {
             if let Some(browse_model) = &model.browse {
@@ -274,24 +581,95 @@ fn view(model: &Model) -> Node<Msg> {
         Mode::About => about::view(&model.ctx),
         Mode::Loading => div!["Loading..."],
     }
-    .deep_map(|node| to_absolute_href(node, &model.ctx.base_path))
 }
 
-// ------ ------
-//     Misc
-// ------ ------
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-fn fix_site_manifest(manifest: SiteManifest) -> SiteManifest {
-    fn to_releative(path: Path) -> Path {
-        if path.is_absolute() {
-            path.releative_to(&Path::new_absolute()).unwrap()
-        } else {
-            path
-        }
+    fn path(path: &str) -> Path {
+        path.parse().unwrap()
+    }
+
+    #[test]
+    fn page_path_from_url_path() {
+        let url = Url::new().set_path(["site", "blog", "post"]);
+        let base_path = Path::new_absolute().join(&path("site"));
+        assert_eq!(
+            url_to_page_path(&url, &base_path, Routing::Path),
+            path("blog/post")
+        );
+        // The hash is not a part of the page path.
+        let url = url.set_hash("/other");
+        assert_eq!(
+            url_to_page_path(&url, &base_path, Routing::Path),
+            path("blog/post")
+        );
     }
-    SiteManifest {
-        index_page: to_releative(manifest.index_page),
-        master_page: to_releative(manifest.master_page),
-        ..manifest
+
+    #[test]
+    fn page_path_from_url_hash() {
+        let base_path = Path::new_absolute().join(&path("site"));
+        let page_path = |hash: &str| {
+            let url = Url::new().set_path(["site"]).set_hash(hash);
+            url_to_page_path(&url, &base_path, Routing::Hash)
+        };
+        assert_eq!(page_path("/blog/post"), path("blog/post"));
+        assert_eq!(page_path("blog//post/"), path("blog/post"));
+        assert_eq!(page_path("/blog/post?tag=x#comments"), path("blog/post"));
+        assert_eq!(page_path(""), Path::new());
+        assert_eq!(page_path("#top"), Path::new());
+        let url = Url::new().set_path(["site", "blog"]);
+        assert_eq!(
+            url_to_page_path(&url, &base_path, Routing::Hash),
+            Path::new()
+        );
+    }
+
+    #[test]
+    fn split_hash_parts() {
+        assert_eq!(split_hash("/blog/post"), ("/blog/post", None, None));
+        assert_eq!(
+            split_hash("/blog?tag=a&page=2#comments"),
+            ("/blog", Some("tag=a&page=2"), Some("comments"))
+        );
+        assert_eq!(split_hash("/blog#top?x"), ("/blog", None, Some("top?x")));
+        assert_eq!(split_hash("?#"), ("", Some(""), Some("")));
+    }
+
+    #[test]
+    fn query_from_url_search() {
+        let url = Url::new().set_search(UrlSearch::new(vec![
+            ("tag", vec!["a", "b"]),
+            ("page", vec!["2"]),
+        ]));
+        let query = url_to_query(&url, Routing::Path);
+        assert_eq!(query["tag"], vec!["a", "b"]);
+        assert_eq!(query["page"], vec!["2"]);
+        assert_eq!(query.len(), 2);
+    }
+
+    #[test]
+    fn fragment_of_url() {
+        let url = Url::new().set_hash("/blog?tag=a#comments");
+        assert_eq!(
+            url_to_fragment(&url, Routing::Path).as_deref(),
+            Some("/blog?tag=a#comments")
+        );
+        assert_eq!(
+            url_to_fragment(&url, Routing::Hash).as_deref(),
+            Some("comments")
+        );
+        let url = Url::new().set_hash("/blog#");
+        assert_eq!(url_to_fragment(&url, Routing::Hash), None);
+        assert_eq!(url_to_fragment(&Url::new(), Routing::Path), None);
+    }
+
+    #[test]
+    fn mode_of_page_path() {
+        assert!(matches!(path_to_mode(&path("_edit/blog")), Mode::Edit));
+        assert!(matches!(path_to_mode(&path("_app")), Mode::About));
+        assert!(matches!(path_to_mode(&path("blog/_edit")), Mode::Browse));
+        assert!(matches!(path_to_mode(&Path::new()), Mode::Browse));
     }
 }
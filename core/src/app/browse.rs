@@ -1,16 +1,19 @@
 use crate::builtin_widgets;
-use crate::constants::DOC_DIR;
 use crate::context::Context;
 use crate::error::FetchError;
-use crate::manifests::{DocManifest, SiteManifest};
+use crate::head::{self, HeadElement, HeadElements};
+use crate::manifests::{DocManifest, PageMeta, SiteManifest};
+use crate::notification::Notification;
 use crate::path::Path;
 use crate::utils;
-use crate::widget::{Dependencies, Widget, WidgetCmd, WidgetMsg, WidgetOrders};
+use crate::widget::driver::{self, Order};
+use crate::widget::{find_cycles, Cycles, Dependencies, Widget, WidgetMsg, WidgetOrders};
 use enclose::enc;
 use failure::{format_err, Error};
 use futures::FutureExt;
+use itertools::Itertools;
 use seed::{prelude::*, *};
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
 // ------ ------
 //     Init
@@ -21,18 +24,46 @@ pub fn init(_url: Url, _orders: &mut impl Orders<Msg>, ctx: &Context) -> Model {
     let page_path = current_page_or_index(ctx);
     let full_path = ctx
         .site_manifest
-        .master_page
-        .clone()
-        .join(&page_path.clone());
+        .master_page_for(&page_path)
+        .join(&page_path);
     Model {
         page_path,
         full_path,
         widgets: BTreeMap::new(),
+        documents: BTreeMap::new(),
         failed: BTreeSet::new(),
         dependencies: BTreeMap::new(),
+        cycles: Cycles::new(),
+        meta: BTreeMap::new(),
+        head: BTreeMap::new(),
+        head_elements: HeadElements::default(),
+        unused: VecDeque::new(),
+        pending: 0,
+        scroll_to_fragment: false,
     }
 }
 
+// Shows single document without master page. Used by edit mode for preview.
+pub(crate) fn init_document(doc_path: Path, orders: &mut impl Orders<Msg>, ctx: &Context) -> Model {
+    let mut model = Model {
+        page_path: doc_path.clone(),
+        full_path: doc_path.clone(),
+        widgets: BTreeMap::new(),
+        documents: BTreeMap::new(),
+        failed: BTreeSet::new(),
+        dependencies: BTreeMap::new(),
+        cycles: Cycles::new(),
+        meta: BTreeMap::new(),
+        head: BTreeMap::new(),
+        head_elements: HeadElements::default(),
+        unused: VecDeque::new(),
+        pending: 0,
+        scroll_to_fragment: false,
+    };
+    load_document(doc_path, &mut model, orders, ctx);
+    model
+}
+
 // ------ ------
 //     Model
 // ------ ------
@@ -43,8 +74,26 @@ pub struct Model {
     page_path: Path,
     full_path: Path,
     widgets: BTreeMap<Path, Box<dyn Widget>>,
+    // Document every loaded path belongs to.
+    documents: BTreeMap<Path, Path>,
+    // Documents shown with the failed widget, they are not kept in the cache.
     failed: BTreeSet<Path>,
     dependencies: BTreeMap<Path, BTreeSet<Path>>,
+    cycles: Cycles,
+    meta: BTreeMap<Path, PageMeta>,
+    head: BTreeMap<Path, BTreeSet<HeadElement>>,
+    head_elements: HeadElements,
+    // Loaded documents not reachable from the current page, most recently used first.
+    unused: VecDeque<Path>,
+    // Commands and messages to itself that are not handled yet.
+    pending: usize,
+    // Scroll to the element of the URL fragment once the page is loaded.
+    scroll_to_fragment: bool,
+}
+
+/// All documents of the page are loaded and no commands are in flight.
+pub(crate) fn is_loaded(model: &Model) -> bool {
+    model.pending == 0
 }
 
 // ------ ------
@@ -55,38 +104,73 @@ pub struct Model {
 #[derive(Debug)]
 pub enum Msg {
     PageChanged(Path),
+    /// Only query parameters of the page URL changed.
+    QueryChanged,
+    ScrollToFragment,
     SiteManifestChanged(SiteManifest),
-    DocManifestFetched(Path, Result<DocManifest, FetchError>),
+    /// Document found for the path, with its manifest.
+    DocManifestFetched(Path, Result<(Path, DocManifest), FetchError>),
     WidgetReady(Path),
     WidgetFailed(Path, Error),
+    ReloadDocument(Path),
     UpdateDependencies(Path, BTreeSet<Path>),
     DependenciesChanged(Path),
+    UpdateHead(Path, BTreeSet<HeadElement>),
+    UpdateMeta(Path, PageMeta),
     WidgetMsg(Path, WidgetMsg),
-    ShowError(Error), // @TODO use subscribtion for errors
 }
 
 // `update` describes how to handle each `Msg`.
 pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>, ctx: &Context) {
     // log!("browse::update", msg);
+    if is_pending_result(&msg) {
+        model.pending = model.pending.saturating_sub(1);
+    }
     match msg {
+        Msg::DocManifestFetched(path, _)
+        | Msg::WidgetReady(path)
+        | Msg::WidgetFailed(path, _)
+        | Msg::ReloadDocument(path)
+        | Msg::UpdateDependencies(path, _)
+        | Msg::UpdateHead(path, _)
+        | Msg::UpdateMeta(path, _)
+            if !model.widgets.contains_key(&path) =>
+        {
+            // Document was unloaded while the message was in flight.
+        }
         Msg::PageChanged(_) => {
             update_current_page(model, orders, ctx);
         }
-        Msg::ShowError(err) => error!(err),
+        Msg::QueryChanged => {
+            for path in reachable_documents(model) {
+                if let Some(widget) = model.widgets.get_mut(&path) {
+                    let result = widget.update(WidgetMsg::QueryChanged, ctx);
+                    handle_widget_result(result, path, model, orders, ctx);
+                }
+            }
+        }
+        Msg::ScrollToFragment => {
+            model.scroll_to_fragment = true;
+        }
         Msg::SiteManifestChanged(_) => {
             update_current_page(model, orders, ctx);
         }
-        Msg::DocManifestFetched(path, result) => {
-            let result = result.map_err(Error::from).and_then(enc!(
-                (path) | manifest | resolve_widget(path, manifest, ctx)
-            ));
-            match result {
+        Msg::DocManifestFetched(path, found) => {
+            if let Ok((document, manifest)) = &found {
+                model.documents.insert(path.clone(), document.clone());
+                model.meta.insert(path.clone(), manifest.meta.clone());
+                if path == model.page_path {
+                    update_head(model, ctx);
+                }
+            }
+            model.pending += 1;
+            match driver::create_widget(&path, found, ctx) {
                 Ok(widget) => {
                     model.widgets.insert(path.clone(), widget);
                     orders.send_msg(Msg::WidgetReady(path));
                 }
                 Err(err) => {
-                    orders.send_msg(Msg::WidgetFailed(path, err.into()));
+                    orders.send_msg(Msg::WidgetFailed(path, err));
                 }
             }
         }
@@ -96,33 +180,38 @@ pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>, ctx: &
                 .get_mut(&path)
                 .ok_or(format_err!("Widget for \"{}\" not found", &path))
                 .and_then(|widget| widget.init(&path, ctx));
-            handle_widget_result(result, path, orders);
+            handle_widget_result(result, path, model, orders, ctx);
         }
         Msg::WidgetFailed(path, error) => {
-            /*
-            if let Some(fetch_err) = error.as_fail().downcast_ref::<FetchError>() {
-                if fetch_err.is_not_found && path != NOT_FOUND_PATH {
-                    TODO load 404 page
-                }
-            }
-            */
+            model.failed.insert(path.clone());
             model.dependencies.remove(&path);
+            model.head.remove(&path);
+            update_cycles(model, orders);
             model.widgets.insert(
                 path.clone(),
-                builtin_widgets::Failed::new(path.clone(), &error),
+                builtin_widgets::Failed::with_retry(path.clone(), &error),
             );
+            model.pending += 1;
             orders
                 .send_msg(Msg::WidgetReady(path))
-                .send_msg(Msg::ShowError(error));
+                .notify(Notification::error(error));
+        }
+        Msg::WidgetMsg(_, WidgetMsg::ForDocument(path, msg)) => {
+            update(Msg::WidgetMsg(path, *msg), model, orders, ctx);
         }
         Msg::WidgetMsg(path, msg) => {
             if let Some(widget) = model.widgets.get_mut(&path) {
                 let result = widget.update(msg, ctx);
-                handle_widget_result(result, path, orders);
-            } else {
-                error!("Got message for unknown widget", path);
+                handle_widget_result(result, path, model, orders, ctx);
             }
         }
+        Msg::ReloadDocument(path) => {
+            model.failed.remove(&path);
+            model.dependencies.remove(&path);
+            model.head.remove(&path);
+            update_cycles(model, orders);
+            load_document(path, model, orders, ctx);
+        }
         Msg::UpdateDependencies(path, dependencies) => {
             use std::collections::btree_map::Entry;
             match model.dependencies.entry(path.clone()) {
@@ -142,7 +231,28 @@ pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>, ctx: &
                 }
             }
         }
-        Msg::DependenciesChanged(_) => {}
+        Msg::DependenciesChanged(_) => {
+            update_cycles(model, orders);
+            collect_garbage(model, ctx);
+        }
+        Msg::UpdateHead(path, elements) => {
+            model.head.insert(path, elements);
+        }
+        Msg::UpdateMeta(path, meta) => {
+            let is_page = path == model.page_path;
+            model.meta.insert(path, meta);
+            if is_page {
+                update_head(model, ctx);
+            }
+        }
+    }
+    update_head_elements(model, ctx);
+
+    if model.scroll_to_fragment && is_loaded(model) {
+        model.scroll_to_fragment = false;
+        if let Some(fragment) = ctx.fragment.clone() {
+            orders.after_next_render(move |_| scroll_to_element(&fragment));
+        }
     }
 }
 
@@ -155,9 +265,16 @@ pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>, ctx: &
 // `view` describes what to display.
 pub fn view(model: &Model, ctx: &Context) -> Node<Msg> {
     let doc_path = model.full_path.clone();
-    Dependencies::new(&model.widgets, &model.dependencies, ctx)
-        .view(&doc_path)
-        .map_msg(move |msg| Msg::WidgetMsg(doc_path, msg))
+    Dependencies::new(
+        &model.widgets,
+        &model.documents,
+        &model.dependencies,
+        &model.cycles,
+        &model.meta,
+        ctx,
+    )
+    .view(&doc_path)
+    .map_msg(move |msg| Msg::WidgetMsg(doc_path, msg))
 }
 
 // ------ ------
@@ -166,7 +283,13 @@ pub fn view(model: &Model, ctx: &Context) -> Node<Msg> {
 
 fn update_current_page(model: &mut Model, orders: &mut impl Orders<Msg>, ctx: &Context) {
     model.page_path = current_page_or_index(ctx);
-    model.full_path = ctx.site_manifest.master_page.clone().join(&model.page_path);
+    model.full_path = ctx
+        .site_manifest
+        .master_page_for(&model.page_path)
+        .join(&model.page_path);
+    update_cycles(model, orders);
+    collect_garbage(model, ctx);
+    update_head(model, ctx);
 
     if !model.widgets.contains_key(&model.full_path) {
         load_document(model.full_path.clone(), model, orders, ctx);
@@ -177,7 +300,8 @@ fn load_document(doc_path: Path, model: &mut Model, orders: &mut impl Orders<Msg
     model
         .widgets
         .insert(doc_path.clone(), loading_widget(&doc_path, ctx));
-    let fut = utils::fetch_doc_manifest(doc_path.head().to_string())
+    model.pending += 1;
+    let fut = utils::find_doc_manifest(ctx.storage.clone(), doc_path.clone(), &ctx.site_manifest)
         .map(enc!((doc_path) move |result| {Msg::DocManifestFetched(doc_path, result)}));
     orders.perform_cmd(fut);
 }
@@ -192,8 +316,7 @@ fn load_dependencies(
         .dependencies
         .get(doc_path)
         .into_iter()
-        .map(BTreeSet::iter)
-        .flatten()
+        .flat_map(BTreeSet::iter)
         .filter(|dep| !model.widgets.contains_key(dep))
         .cloned()
         .collect();
@@ -202,17 +325,6 @@ fn load_dependencies(
     }
 }
 
-fn resolve_widget(
-    doc_path: Path,
-    manifest: DocManifest,
-    ctx: &Context,
-) -> Result<Box<dyn Widget>, Error> {
-    Ok(ctx
-        .registry
-        .get_widget(&manifest.widget)?
-        .create(doc_path, manifest)?)
-}
-
 fn current_page_or_index(ctx: &Context) -> Path {
     if !ctx.page_path.is_empty() {
         ctx.page_path.clone()
@@ -226,61 +338,209 @@ fn current_page_or_index(ctx: &Context) -> Path {
 fn handle_widget_result(
     result: Result<Option<WidgetOrders>, Error>,
     path: Path,
+    model: &mut Model,
     orders: &mut impl Orders<Msg>,
+    ctx: &Context,
 ) {
     match result {
         Ok(Some(w_orders)) => {
-            perform_widget_orders(w_orders, path, orders);
+            perform_widget_orders(w_orders, path, model, orders, ctx);
         }
         Ok(None) => (),
         Err(err) => {
+            model.pending += 1;
             orders.send_msg(Msg::WidgetFailed(path, err));
         }
     }
 }
 
-fn perform_widget_orders(w_orders: WidgetOrders, doc_path: Path, orders: &mut impl Orders<Msg>) {
+fn perform_widget_orders(
+    w_orders: WidgetOrders,
+    doc_path: Path,
+    model: &mut Model,
+    orders: &mut impl Orders<Msg>,
+    ctx: &Context,
+) {
     #[cfg(debug_assertions)]
     log!("perform_widget_orders", doc_path, w_orders.orders);
-    w_orders.orders.into_iter().for_each(|cmd| {
-        let doc_path = doc_path.clone();
-        match cmd {
-            WidgetCmd::FetchBytes(path) => {
-                let full_file_path = Path::new().add(DOC_DIR).join(&doc_path.head()).join(&path);
-                let fut = utils::fetch_bytes(full_file_path).map(enc!((doc_path) move |result| {
-                    Msg::WidgetMsg(doc_path.clone(), WidgetMsg::FetchBytesResult(path, result))
-                }));
-                orders.perform_cmd(fut);
-            }
-            WidgetCmd::FetchJson(path) => {
-                let full_file_path = Path::new().add(DOC_DIR).join(&doc_path.head()).join(&path);
-                let fut = utils::fetch_json::<_, serde_json::Value>(full_file_path).map(
-                    enc!((doc_path) move |result| {
-                        Msg::WidgetMsg(doc_path.clone(), WidgetMsg::FetchJsonResult(path, result))
-                    }),
+    let document = model
+        .documents
+        .get(&doc_path)
+        .cloned()
+        .unwrap_or_else(|| doc_path.clone());
+    for order in driver::interpret_orders(w_orders, &document, ctx) {
+        match order {
+            Order::Notify(_) | Order::Skip => (),
+            _ => model.pending += 1,
+        }
+        match order {
+            Order::Perform(fut) => {
+                orders.perform_cmd(
+                    fut.map(enc!((doc_path) move |msg| Msg::WidgetMsg(doc_path, msg))),
                 );
-                orders.perform_cmd(fut);
             }
-            WidgetCmd::FetchText(path) => {
-                let full_file_path = Path::new().add(DOC_DIR).join(&doc_path.head()).join(&path);
-                let fut = utils::fetch_text(full_file_path).map(enc!((doc_path) move |result| {
-                    Msg::WidgetMsg(doc_path.clone(), WidgetMsg::FetchTextResult(path, result))
-                }));
-                orders.perform_cmd(fut);
+            Order::Notify(notification) => {
+                orders.notify(notification);
             }
-            WidgetCmd::PerformCmd(fut) => {
-                orders.perform_cmd(
-                    fut.map(|result| Msg::WidgetMsg(doc_path, WidgetMsg::CmdResult(result))),
-                );
+            Order::Reload => {
+                orders.send_msg(Msg::ReloadDocument(doc_path.clone()));
+            }
+            Order::UpdateDependencies(dependencies) => {
+                orders.send_msg(Msg::UpdateDependencies(doc_path.clone(), dependencies));
+            }
+            Order::UpdateHead(elements) => {
+                orders.send_msg(Msg::UpdateHead(doc_path.clone(), elements));
             }
-            WidgetCmd::UpdateDependencies(dependencies) => {
-                orders.send_msg(Msg::UpdateDependencies(doc_path, dependencies));
+            Order::UpdateMeta(meta) => {
+                orders.send_msg(Msg::UpdateMeta(doc_path.clone(), meta));
             }
-            WidgetCmd::Skip => {
+            Order::Skip => {
                 orders.skip();
             }
+            Order::Fail(err) => {
+                orders.send_msg(Msg::WidgetFailed(doc_path.clone(), err));
+            }
+        }
+    }
+}
+
+// Searches cycles from the current page and reports new ones.
+fn update_cycles(model: &mut Model, orders: &mut impl Orders<Msg>) {
+    let cycles = find_cycles(&model.dependencies, &model.full_path);
+    for cycle in cycles.values() {
+        if !model.cycles.values().any(|known| known == cycle) {
+            orders.notify(Notification::error(format!(
+                "Dependency cycle: {}",
+                cycle.iter().join(" -> ")
+            )));
         }
-    })
+    }
+    model.cycles = cycles;
+}
+
+// Unloads documents not reachable from the current page
+// except `ctx.cache_size` most recently used ones.
+// Failed documents are unloaded right away, so they are loaded again when needed.
+fn collect_garbage(model: &mut Model, ctx: &Context) {
+    let reachable = reachable_documents(model);
+
+    let failed: Vec<Path> = model
+        .failed
+        .iter()
+        .filter(|path| !reachable.contains(*path))
+        .cloned()
+        .collect();
+    for path in failed {
+        unload_document(&path, model);
+    }
+
+    model.unused.retain(|path| !reachable.contains(path));
+    let newly_unused: Vec<Path> = model
+        .widgets
+        .keys()
+        .filter(|path| !reachable.contains(*path) && !model.unused.contains(path))
+        .cloned()
+        .collect();
+    for path in newly_unused {
+        model.unused.push_front(path);
+    }
+    while model.unused.len() > ctx.cache_size {
+        if let Some(path) = model.unused.pop_back() {
+            unload_document(&path, model);
+        }
+    }
+}
+
+fn unload_document(path: &Path, model: &mut Model) {
+    model.widgets.remove(path);
+    model.documents.remove(path);
+    model.dependencies.remove(path);
+    model.meta.remove(path);
+    model.head.remove(path);
+    model.failed.remove(path);
+    model.unused.retain(|unused| unused != path);
+}
+
+// Documents shown on the current page.
+fn reachable_documents(model: &Model) -> BTreeSet<Path> {
+    let mut reachable = BTreeSet::new();
+    let mut to_visit = vec![model.full_path.clone()];
+    while let Some(path) = to_visit.pop() {
+        if let Some(deps) = model.dependencies.get(&path) {
+            to_visit.extend(deps.iter().filter(|dep| !reachable.contains(*dep)).cloned());
+        }
+        reachable.insert(path);
+    }
+    reachable
+}
+
+// Puts head elements of documents shown on the current page into the document head.
+// Documents no longer shown release theirs.
+fn update_head_elements(model: &mut Model, ctx: &Context) {
+    let reachable = reachable_documents(model);
+    let mut result = Ok(());
+    for (path, elements) in model
+        .head
+        .iter()
+        .filter(|(path, _)| reachable.contains(*path))
+    {
+        result = result.and(model.head_elements.set(path, elements, &ctx.base_path));
+    }
+    let released: Vec<Path> = model
+        .head_elements
+        .owners()
+        .filter(|path| !reachable.contains(*path) || !model.head.contains_key(*path))
+        .cloned()
+        .collect();
+    for path in released {
+        model.head_elements.release(&path);
+    }
+    if is_loaded(model) {
+        result = result.and(model.head_elements.remove_prerendered());
+    }
+    if let Err(err) = result {
+        error!("Can not update document head", err);
+    }
+}
+
+// Shows metadata of the current page in the document head.
+fn update_head(model: &Model, ctx: &Context) {
+    let meta = model
+        .meta
+        .get(&model.page_path)
+        .cloned()
+        .unwrap_or_default()
+        .or_site(&ctx.site_manifest.meta);
+    if let Err(err) = head::apply_page_meta(&meta) {
+        error!("Can not update document head", err);
+    }
+}
+
+fn scroll_to_element(id: &str) {
+    if let Some(el) = seed::document().get_element_by_id(id) {
+        el.scroll_into_view();
+    }
+}
+
+// Results of commands and messages counted in `Model::pending`.
+fn is_pending_result(msg: &Msg) -> bool {
+    matches!(
+        msg,
+        Msg::DocManifestFetched(..)
+            | Msg::WidgetReady(_)
+            | Msg::WidgetFailed(..)
+            | Msg::ReloadDocument(_)
+            | Msg::UpdateDependencies(..)
+            | Msg::UpdateHead(..)
+            | Msg::UpdateMeta(..)
+            | Msg::WidgetMsg(
+                _,
+                WidgetMsg::FetchBytesResult(..)
+                    | WidgetMsg::FetchJsonResult(..)
+                    | WidgetMsg::FetchTextResult(..)
+                    | WidgetMsg::CmdResult(_),
+            )
+    )
 }
 
 fn loading_widget(doc_path: &Path, ctx: &Context) -> Box<dyn Widget> {
@@ -291,7 +551,6 @@ fn loading_widget(doc_path: &Path, ctx: &Context) -> Box<dyn Widget> {
     ctx.registry
         .get_widget("loading")
         .ok()
-        .map(|factory| factory.create(doc_path.clone(), manifest).ok())
-        .flatten()
+        .and_then(|factory| factory.create(doc_path.clone(), Path::new(), manifest).ok())
         .unwrap_or_else(builtin_widgets::Loading::new)
 }
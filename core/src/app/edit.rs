@@ -1,13 +1,31 @@
+use crate::app::browse;
 use crate::context::Context;
-use crate::manifests::SiteManifest;
+use crate::error::FetchError;
+use crate::manifests::{DocManifest, SiteManifest};
+use crate::notification::Notification;
+use crate::path::Path;
+use crate::utils;
+use crate::widget::WidgetMsg;
+use bytes::Bytes;
+use enclose::enc;
+use futures::FutureExt;
 use seed::{prelude::*, *};
+use std::collections::BTreeMap;
 
 // ------ ------
 //     Init
 // ------ ------
 
-pub fn init(url: Url, orders: &mut impl Orders<Msg>, ctx: &Context) -> Model {
-    Model {}
+pub fn init(_url: Url, orders: &mut impl Orders<Msg>, ctx: &Context) -> Model {
+    let doc_path = edited_document(ctx);
+    Model {
+        doc_path: doc_path.clone(),
+        document: doc_path.clone(),
+        manifest: None,
+        sources: BTreeMap::new(),
+        selected: None,
+        preview: browse::init_document(doc_path, &mut orders.proxy(Msg::PreviewMsg), ctx),
+    }
 }
 
 // ------ ------
@@ -15,7 +33,27 @@ pub fn init(url: Url, orders: &mut impl Orders<Msg>, ctx: &Context) -> Model {
 // ------ ------
 
 #[derive(Debug)]
-pub struct Model {}
+pub struct Model {
+    doc_path: Path,
+    // Document of `doc_path`, sources are saved to its directory.
+    document: Path,
+    manifest: Option<DocManifest>,
+    sources: BTreeMap<Path, Source>,
+    selected: Option<Path>,
+    preview: browse::Model,
+}
+
+#[derive(Debug)]
+struct Source {
+    original: String,
+    text: String,
+}
+
+impl Source {
+    fn is_modified(&self) -> bool {
+        self.original != self.text
+    }
+}
 
 // ------ ------
 //    Update
@@ -25,14 +63,273 @@ pub struct Model {}
 pub enum Msg {
     UrlChanged(Url),
     SiteManifestChanged(SiteManifest),
+    SelectSource(Path),
+    SourceChanged(Path, String),
+    RevertSource(Path),
+    SaveSource(Path),
+    SourceSaved(Path, String, Result<(), FetchError>),
+    PreviewMsg(browse::Msg),
 }
 
-pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>, ctx: &Context) {}
+pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>, ctx: &Context) {
+    match msg {
+        Msg::UrlChanged(_) | Msg::SiteManifestChanged(_) => {
+            let doc_path = edited_document(ctx);
+            if doc_path != model.doc_path {
+                *model = init(ctx.url.clone(), orders, ctx);
+            }
+        }
+        Msg::SelectSource(file_path) => {
+            model.selected.replace(file_path);
+        }
+        Msg::SourceChanged(file_path, text) => {
+            if let Some(source) = model.sources.get_mut(&file_path) {
+                source.text = text.clone();
+                send_to_preview(file_path, text, model, orders, ctx);
+            }
+        }
+        Msg::RevertSource(file_path) => {
+            if let Some(source) = model.sources.get_mut(&file_path) {
+                source.text = source.original.clone();
+                let text = source.text.clone();
+                send_to_preview(file_path, text, model, orders, ctx);
+            }
+        }
+        Msg::SaveSource(file_path) => {
+            if let Some(source) = model.sources.get(&file_path) {
+                let text = source.text.clone();
+                match utils::doc_file_path(&model.document, &file_path) {
+                    Ok(full_file_path) => {
+                        let fut = ctx
+                            .storage
+                            .write(&full_file_path, Bytes::from(text.clone()))
+                            .map(move |result| Msg::SourceSaved(file_path, text, result));
+                        orders.perform_cmd(fut);
+                    }
+                    Err(err) => {
+                        orders.notify(Notification::error(err));
+                    }
+                }
+            }
+        }
+        Msg::SourceSaved(file_path, text, Ok(())) => {
+            if let Some(source) = model.sources.get_mut(&file_path) {
+                source.original = text;
+            }
+        }
+        Msg::SourceSaved(_, _, Err(err)) => {
+            orders.notify(Notification::error(err));
+        }
+        Msg::PreviewMsg(preview_msg) => {
+            // Sources are taken from what the edited widget requested itself.
+            match &preview_msg {
+                browse::Msg::DocManifestFetched(path, Ok((document, manifest)))
+                    if *path == model.doc_path =>
+                {
+                    model.document = document.clone();
+                    model.manifest.replace(manifest.clone());
+                }
+                browse::Msg::WidgetMsg(path, WidgetMsg::FetchTextResult(file_path, Ok(text)))
+                    if *path == model.doc_path =>
+                {
+                    model.sources.insert(
+                        file_path.clone(),
+                        Source {
+                            original: text.clone(),
+                            text: text.clone(),
+                        },
+                    );
+                    if model.selected.is_none() {
+                        model.selected.replace(file_path.clone());
+                    }
+                }
+                _ => (),
+            }
+            browse::update(
+                preview_msg,
+                &mut model.preview,
+                &mut orders.proxy(Msg::PreviewMsg),
+                ctx,
+            );
+        }
+    }
+}
+
+fn send_to_preview(
+    file_path: Path,
+    text: String,
+    model: &mut Model,
+    orders: &mut impl Orders<Msg>,
+    ctx: &Context,
+) {
+    browse::update(
+        browse::Msg::WidgetMsg(
+            model.doc_path.clone(),
+            WidgetMsg::FetchTextResult(file_path, Ok(text)),
+        ),
+        &mut model.preview,
+        &mut orders.proxy(Msg::PreviewMsg),
+        ctx,
+    );
+}
+
+fn edited_document(ctx: &Context) -> Path {
+    let doc_path = ctx.page_path.tail();
+    if !doc_path.is_empty() {
+        doc_path
+    } else if !ctx.site_manifest.index_page.is_empty() {
+        ctx.site_manifest.index_page.clone()
+    } else {
+        "index".parse().unwrap()
+    }
+}
 
 // ------ ------
 //     View
 // ------ ------
 
 pub fn view(model: &Model, ctx: &Context) -> Node<Msg> {
-    div! {"Not implemented"}
+    div![
+        C!["edit"],
+        view_header(model),
+        div![
+            C!["edit-panes"],
+            style! {St::Display => "flex"},
+            div![
+                C!["edit-sources"],
+                style! {St::Flex => "1"},
+                view_sources(model, ctx)
+            ],
+            div![
+                C!["edit-preview"],
+                style! {St::Flex => "1"},
+                browse::view(&model.preview, ctx).map_msg(Msg::PreviewMsg)
+            ],
+        ]
+    ]
+}
+
+fn view_header(model: &Model) -> Node<Msg> {
+    div![
+        C!["edit-header"],
+        h2![format!("Document: {}", model.doc_path)],
+        model
+            .manifest
+            .as_ref()
+            .map(|manifest| div![format!("Widget: {}", manifest.widget)]),
+    ]
+}
+
+fn view_sources(model: &Model, ctx: &Context) -> Vec<Node<Msg>> {
+    if model.sources.is_empty() {
+        return vec![div!["No editable sources"]];
+    }
+    let tabs = ul![
+        C!["edit-tabs"],
+        model.sources.iter().map(|(file_path, source)| {
+            let is_selected = model.selected.as_ref() == Some(file_path);
+            li![
+                C![IF!(is_selected => "selected")],
+                a![
+                    format!(
+                        "{}{}",
+                        file_path,
+                        if source.is_modified() { " *" } else { "" }
+                    ),
+                    ev(
+                        Ev::Click,
+                        enc!((file_path) move |_| Msg::SelectSource(file_path))
+                    ),
+                ]
+            ]
+        })
+    ];
+    let editor = model.selected.as_ref().and_then(|file_path| {
+        model.sources.get(file_path).map(|source| {
+            div![
+                C!["edit-editor"],
+                textarea![
+                    attrs! {
+                        At::Value => source.text,
+                        At::Rows => 32,
+                        At::SpellCheck => "false",
+                    },
+                    style! {St::Width => "100%"},
+                    input_ev(
+                        Ev::Input,
+                        enc!((file_path) move |text| Msg::SourceChanged(file_path, text))
+                    ),
+                ],
+                button![
+                    "Revert",
+                    attrs! {At::Disabled => (!source.is_modified()).as_at_value()},
+                    ev(
+                        Ev::Click,
+                        enc!((file_path) move |_| Msg::RevertSource(file_path))
+                    ),
+                ],
+                IF!(ctx.storage.can_write() => button![
+                    "Save",
+                    attrs! {At::Disabled => (!source.is_modified()).as_at_value()},
+                    ev(
+                        Ev::Click,
+                        enc!((file_path) move |_| Msg::SaveSource(file_path))
+                    ),
+                ]),
+            ]
+        })
+    });
+    vec![tabs, editor.unwrap_or_else(|| div![])]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::{Registry, Routing};
+    use crate::storage::MemoryStorage;
+    use std::rc::Rc;
+
+    fn ctx(page_path: &str, site_manifest: &str) -> Context {
+        let site_manifest: SiteManifest = serde_json::from_str(site_manifest).unwrap();
+        Context {
+            url: Url::new(),
+            page_path: page_path.parse().unwrap(),
+            query: BTreeMap::new(),
+            fragment: None,
+            base_path: Path::new_absolute(),
+            routing: Routing::default(),
+            site_manifest: site_manifest.normalized(),
+            registry: Registry::builtin(),
+            storage: Rc::new(MemoryStorage::new()),
+            cache_size: 0,
+        }
+    }
+
+    fn path(path: &str) -> Path {
+        path.parse().unwrap()
+    }
+
+    #[test]
+    fn edit_document_of_page_path() {
+        let site_manifest = r#"{"indexPage": "home"}"#;
+        assert_eq!(
+            edited_document(&ctx("_edit/blog/post", site_manifest)),
+            path("blog/post")
+        );
+        assert_eq!(edited_document(&ctx("_edit", site_manifest)), path("home"));
+        assert_eq!(edited_document(&ctx("_edit", "{}")), path("index"));
+    }
+
+    #[test]
+    fn source_modification() {
+        let mut source = Source {
+            original: "# Title".to_string(),
+            text: "# Title".to_string(),
+        };
+        assert!(!source.is_modified());
+        source.text.push_str("\nText");
+        assert!(source.is_modified());
+        source.original = source.text.clone();
+        assert!(!source.is_modified());
+    }
 }
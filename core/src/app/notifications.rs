@@ -0,0 +1,156 @@
+use crate::notification::{Notification, Severity};
+use seed::{prelude::*, *};
+
+const INFO_TIMEOUT_MS: u32 = 5000;
+
+// ------ ------
+//     Model
+// ------ ------
+
+#[derive(Debug, Default)]
+pub struct Model {
+    entries: Vec<Entry>,
+    next_id: usize,
+}
+
+#[derive(Debug)]
+struct Entry {
+    id: usize,
+    notification: Notification,
+    count: usize,
+}
+
+impl Model {
+    // Id of the new entry, `None` if the same notification is shown already.
+    fn add(&mut self, notification: Notification) -> Option<usize> {
+        // Same errors tend to come in bursts, e.g. when several documents
+        // fail to fetch for the same reason.
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.notification == notification)
+        {
+            entry.count += 1;
+            return None;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.push(Entry {
+            id,
+            notification,
+            count: 1,
+        });
+        Some(id)
+    }
+
+    fn dismiss(&mut self, id: usize) {
+        self.entries.retain(|entry| entry.id != id);
+    }
+}
+
+// ------ ------
+//    Update
+// ------ ------
+
+#[derive(Debug)]
+pub enum Msg {
+    Show(Notification),
+    Dismiss(usize),
+}
+
+pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
+    match msg {
+        Msg::Show(notification) => {
+            match notification.severity {
+                Severity::Error => error!(notification.message),
+                Severity::Warning | Severity::Info => log!(notification.message),
+            }
+            let is_info = notification.severity == Severity::Info;
+            if let Some(id) = model.add(notification) {
+                if is_info {
+                    orders.perform_cmd(cmds::timeout(INFO_TIMEOUT_MS, move || Msg::Dismiss(id)));
+                }
+            }
+        }
+        Msg::Dismiss(id) => {
+            model.dismiss(id);
+        }
+    }
+}
+
+// ------ ------
+//     View
+// ------ ------
+
+pub fn view(model: &Model) -> Node<Msg> {
+    if model.entries.is_empty() {
+        return empty![];
+    }
+    div![
+        C!["notifications"],
+        style! {
+            St::Position => "fixed",
+            St::Top => "0",
+            St::Right => "0",
+            St::ZIndex => "1000",
+        },
+        model.entries.iter().map(view_entry),
+    ]
+}
+
+fn view_entry(entry: &Entry) -> Node<Msg> {
+    let id = entry.id;
+    div![
+        C!["notification", entry.notification.severity.as_str()],
+        span![C!["notification-message"], &entry.notification.message],
+        IF!(entry.count > 1 => span![
+            C!["notification-count"],
+            format!(" ({})", entry.count)
+        ]),
+        button![
+            C!["notification-dismiss"],
+            "×",
+            ev(Ev::Click, move |_| Msg::Dismiss(id)),
+        ],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(model: &Model) -> Vec<(&str, usize)> {
+        model
+            .entries
+            .iter()
+            .map(|entry| (entry.notification.message.as_str(), entry.count))
+            .collect()
+    }
+
+    #[test]
+    fn count_repeated_notifications() {
+        let mut model = Model::default();
+        let first = model.add(Notification::error("Can not fetch"));
+        assert_eq!(model.add(Notification::error("Can not fetch")), None);
+        // Same message of other severity is another notification.
+        let warning = model.add(Notification::warning("Can not fetch"));
+        assert!(first.is_some() && warning.is_some() && first != warning);
+        assert_eq!(
+            messages(&model),
+            vec![("Can not fetch", 2), ("Can not fetch", 1)]
+        );
+    }
+
+    #[test]
+    fn dismiss_notification() {
+        let mut model = Model::default();
+        let first = model.add(Notification::info("First")).unwrap();
+        model.add(Notification::info("Second")).unwrap();
+        model.dismiss(first);
+        assert_eq!(messages(&model), vec![("Second", 1)]);
+        // Dismissed notification is shown again when it comes next time.
+        let again = model.add(Notification::info("First")).unwrap();
+        assert_ne!(again, first);
+        assert_eq!(messages(&model), vec![("Second", 1), ("First", 1)]);
+    }
+}
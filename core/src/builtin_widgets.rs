@@ -4,3 +4,5 @@ pub use failed::Failed;
 mod loading;
 pub use loading::{Loading, LoadingFactory};
 
+mod not_found;
+pub use not_found::NotFound;
@@ -1,7 +1,7 @@
 use crate::context::Context;
 use crate::path::Path;
-use crate::widget::{Dependencies, Widget, WidgetMsg};
-use failure::AsFail;
+use crate::widget::{Dependencies, Widget, WidgetMsg, WidgetOrders};
+use failure::{AsFail, Error};
 use seed::{prelude::*, *};
 
 const WIDGET_NAME: &'static str = "failed";
@@ -11,18 +11,39 @@ const WIDGET_CLASSES: &'static [&'static str] = &[WIDGET_NAME];
 pub struct Failed {
     doc_path: Path,
     error: String,
+    can_retry: bool,
 }
 
+#[derive(Debug)]
+struct Retry;
+
 impl Failed {
     pub fn new(doc_path: Path, error: &impl AsFail) -> Box<dyn Widget> {
         Box::new(Self {
             doc_path,
             error: error.as_fail().to_string(),
+            can_retry: false,
+        })
+    }
+    /// Shows "Retry" button that loads the document again.
+    pub fn with_retry(doc_path: Path, error: &impl AsFail) -> Box<dyn Widget> {
+        Box::new(Self {
+            doc_path,
+            error: error.as_fail().to_string(),
+            can_retry: true,
         })
     }
 }
 
 impl Widget for Failed {
+    fn update(&mut self, msg: WidgetMsg, _ctx: &Context) -> Result<Option<WidgetOrders>, Error> {
+        match msg {
+            WidgetMsg::Custom(msg) if self.can_retry && msg.is::<Retry>() => {
+                Ok(Some(WidgetOrders::new().reload()))
+            }
+            _ => Ok(None),
+        }
+    }
     fn view(&self, _dependencies: Dependencies, _ctx: &Context) -> Node<WidgetMsg> {
         div![
             C!["failed-widget"],
@@ -31,7 +52,11 @@ impl Widget for Failed {
             },
             h2!["Error"],
             div![format!("Document: {}", self.doc_path)],
-            pre![&self.error]
+            pre![&self.error],
+            IF!(self.can_retry => button![
+                "Retry",
+                ev(Ev::Click, |_| WidgetMsg::Custom(Box::new(Retry))),
+            ]),
         ]
     }
 
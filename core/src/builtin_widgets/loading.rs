@@ -45,7 +45,7 @@ impl WidgetFactory for LoadingFactory {
         const CAN_HANDLE: &'static [&'static str] = &["loading"];
         CAN_HANDLE
     }
-    fn create(&self, _: Path, _: DocManifest) -> Result<Box<dyn Widget>, WidgetError> {
+    fn create(&self, _: Path, _: Path, _: DocManifest) -> Result<Box<dyn Widget>, WidgetError> {
         Ok(Loading::new())
     }
 }
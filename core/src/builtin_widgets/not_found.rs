@@ -0,0 +1,53 @@
+use crate::context::Context;
+use crate::path::Path;
+use crate::widget::{Dependencies, Widget, WidgetMsg, WidgetOrders};
+use failure::Error;
+use seed::{prelude::*, *};
+
+const WIDGET_NAME: &'static str = "not-found";
+const WIDGET_CLASSES: &'static [&'static str] = &[WIDGET_NAME];
+
+#[derive(Debug)]
+pub struct NotFound {
+    doc_path: Path,
+    not_found_page: Path,
+}
+
+impl NotFound {
+    pub fn new(doc_path: Path, not_found_page: Path) -> Box<dyn Widget> {
+        Box::new(Self {
+            doc_path,
+            not_found_page,
+        })
+    }
+}
+
+impl Widget for NotFound {
+    fn init(&mut self, _path: &Path, _ctx: &Context) -> Result<Option<WidgetOrders>, Error> {
+        if self.not_found_page.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(WidgetOrders::new().update_deps(
+                vec![self.not_found_page.clone()].into_iter().collect(),
+            )))
+        }
+    }
+    fn view(&self, dependencies: Dependencies, _ctx: &Context) -> Node<WidgetMsg> {
+        if self.not_found_page.is_empty() {
+            div![
+                C!["not-found-widget"],
+                h2!["Not found"],
+                div![format!("Document: {}", self.doc_path)],
+            ]
+        } else {
+            div![dependencies.view(&self.not_found_page)]
+        }
+    }
+
+    fn widget_name(&self) -> &'static str {
+        WIDGET_NAME
+    }
+    fn classes(&self) -> &'static [&'static str] {
+        WIDGET_CLASSES
+    }
+}
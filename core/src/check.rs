@@ -0,0 +1,308 @@
+//! Offline site validation.
+//!
+//! Loads the whole site tree from a storage without running widgets
+//! and reports broken manifests, unknown widgets, missing documents
+//! and dependency cycles.
+
+use crate::constants::{DOC_DIR, DOC_MANIFEST_FILE, SITE_MANIFEST_FILE};
+use crate::context::Registry;
+use crate::error::FetchError;
+use crate::manifests::{DocManifest, SiteManifest};
+use crate::path::Path;
+use crate::storage::{self, Storage};
+use crate::utils;
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
+
+/// Place in a site file, relative to the site root.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub file: Path,
+    pub line: Option<usize>,
+}
+
+impl Location {
+    pub fn new(file: Path) -> Self {
+        Self { file, line: None }
+    }
+    pub fn line(self, line: usize) -> Self {
+        Self {
+            line: Some(line),
+            ..self
+        }
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}", self.file, line),
+            None => write!(f, "{}", self.file),
+        }
+    }
+}
+
+/// Dependency on the page `target` found at `location`.
+#[derive(Clone, Debug)]
+pub struct Reference {
+    pub target: Path,
+    pub location: Location,
+}
+
+impl Reference {
+    pub fn new(target: Path, location: Location) -> Self {
+        Self { target, location }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Issue {
+    pub location: Location,
+    pub message: String,
+}
+
+impl Issue {
+    fn new(location: Location, message: impl ToString) -> Self {
+        Self {
+            location,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Checks every manifest and page dependency of the site.
+/// Documents are found by listing the storage, so storages that
+/// can not list, like `HttpStorage`, fail with `FetchError::StorageError`
+/// before anything is read. Other problems are returned as issues.
+pub async fn check_site(
+    registry: &Registry,
+    storage: Rc<dyn Storage>,
+) -> Result<Vec<Issue>, FetchError> {
+    let doc_root = Path::new().add(DOC_DIR);
+    if !storage.can_list() {
+        return Err(FetchError::StorageError(
+            doc_root.to_string(),
+            "storage can not list documents".to_string(),
+        ));
+    }
+    let mut issues = vec![];
+    let site_manifest_file: Path = SITE_MANIFEST_FILE.parse().unwrap();
+    let site_manifest =
+        match storage::read_json::<SiteManifest>(&*storage, &site_manifest_file).await {
+            Ok(manifest) => manifest.normalized(),
+            Err(err) => {
+                issues.push(Issue::new(Location::new(site_manifest_file.clone()), err));
+                SiteManifest::default()
+            }
+        };
+
+    let mut documents = BTreeMap::new();
+    // Documents with already reported problems.
+    let mut broken = BTreeSet::new();
+    for file in storage.list(&doc_root).await? {
+        if file.len() < 2 || file[file.len() - 1] != *DOC_MANIFEST_FILE {
+            continue;
+        }
+        let doc_path: Path = file.iter().take(file.len() - 1).collect();
+        let manifest_file = doc_root.join(&file);
+        let location = Location::new(manifest_file.clone());
+        let manifest = match storage::read_json::<DocManifest>(&*storage, &manifest_file).await {
+            Ok(manifest) => manifest,
+            Err(err) => {
+                issues.push(Issue::new(location, err));
+                broken.insert(doc_path);
+                continue;
+            }
+        };
+        let factory = match registry.get_widget(&manifest.widget) {
+            Ok(factory) => factory,
+            Err(err) => {
+                issues.push(Issue::new(location, err));
+                broken.insert(doc_path);
+                continue;
+            }
+        };
+        if let Err(err) = factory.create(doc_path.clone(), Path::new(), manifest.clone()) {
+            issues.push(Issue::new(location, err));
+            broken.insert(doc_path);
+            continue;
+        }
+        documents.insert(doc_path, (manifest, factory, location));
+    }
+
+    // Pages of the site manifest and every document are the roots of the graph.
+    // Redirected pages are checked at their final target, loops are reported below.
+    let is_redirected = |page: &Path| {
+        site_manifest
+            .redirects
+            .keys()
+            .any(|from| page == from || page.is_subpath(from))
+    };
+    let mut pending: Vec<Reference> = site_manifest
+        .pages()
+        .into_iter()
+        .filter(|page| !page.is_empty())
+        .filter_map(|page| {
+            if is_redirected(page) {
+                site_manifest.redirect(page).map(|redirect| redirect.to)
+            } else {
+                Some(page.clone())
+            }
+        })
+        .map(|page| Reference::new(page, Location::new(site_manifest_file.clone())))
+        .collect();
+    pending.extend(
+        documents
+            .iter()
+            .map(|(doc_path, (_, _, location))| Reference::new(doc_path.clone(), location.clone())),
+    );
+
+    let mut graph: BTreeMap<Path, Vec<Reference>> = BTreeMap::new();
+    let mut missing = BTreeSet::new();
+    while let Some(reference) = pending.pop() {
+        let page = reference.target;
+        if graph.contains_key(&page) {
+            continue;
+        }
+        let split = utils::split_doc_path(&page, |doc_path| {
+            documents.contains_key(doc_path) || broken.contains(doc_path)
+        });
+        let (doc_path, sub_path) = match split {
+            Some(split) => split,
+            None => {
+                if missing.insert((reference.location.clone(), page.clone())) {
+                    issues.push(Issue::new(
+                        reference.location,
+                        format!("Document \"{}\" not found", page),
+                    ));
+                }
+                continue;
+            }
+        };
+        let (manifest, factory, location) = match documents.get(&doc_path) {
+            Some(document) => document,
+            None => continue,
+        };
+        let dependencies = match factory
+            .static_dependencies(&doc_path, &sub_path, manifest, storage.clone())
+            .await
+        {
+            Ok(dependencies) => dependencies,
+            Err(err) => {
+                issues.push(Issue::new(location.clone(), err));
+                vec![]
+            }
+        };
+        let dependencies: Vec<_> = dependencies
+            .into_iter()
+            .filter(|dependency| !dependency.target.is_empty())
+            .collect();
+        pending.extend(dependencies.iter().cloned());
+        graph.insert(page, dependencies);
+    }
+
+    for from in site_manifest.redirects.keys() {
+        if site_manifest.redirect(from).is_none() {
+            issues.push(Issue::new(
+                Location::new(site_manifest_file.clone()),
+                format!("Redirect loop from \"{}\"", from),
+            ));
+        }
+    }
+
+    issues.extend(find_cycles(&graph));
+    issues.sort();
+    issues.dedup();
+    Ok(issues)
+}
+
+/// Reports every dependency that closes a cycle.
+fn find_cycles(graph: &BTreeMap<Path, Vec<Reference>>) -> Vec<Issue> {
+    fn visit(
+        page: &Path,
+        graph: &BTreeMap<Path, Vec<Reference>>,
+        stack: &mut Vec<Path>,
+        done: &mut BTreeSet<Path>,
+        issues: &mut Vec<Issue>,
+    ) {
+        stack.push(page.clone());
+        for reference in graph.get(page).into_iter().flatten() {
+            let target = &reference.target;
+            if let Some(start) = stack.iter().position(|p| p == target) {
+                let cycle: Vec<String> = stack[start..]
+                    .iter()
+                    .chain(std::iter::once(target))
+                    .map(|p| p.to_string())
+                    .collect();
+                issues.push(Issue::new(
+                    reference.location.clone(),
+                    format!("Dependency cycle: {}", cycle.join(" -> ")),
+                ));
+            } else if !done.contains(target) {
+                visit(target, graph, stack, done, issues);
+            }
+        }
+        stack.pop();
+        done.insert(page.clone());
+    }
+
+    let mut issues = vec![];
+    let mut done = BTreeSet::new();
+    for page in graph.keys() {
+        if !done.contains(page) {
+            visit(page, graph, &mut vec![], &mut done, &mut issues);
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use bytes::Bytes;
+    use futures::executor::block_on;
+    use futures::future::{self, FutureExt, LocalBoxFuture};
+
+    #[test]
+    fn check_listed_documents() {
+        let storage = MemoryStorage::new()
+            .file("site_manifest.json", r#"{"indexPage": "blog"}"#)
+            .file("_doc/blog/doc_manifest.json", r#"{"widget": "markdown"}"#)
+            .file("_doc/blog/text.md", "# Blog");
+        let issues = block_on(check_site(&Registry::builtin(), Rc::new(storage))).unwrap();
+        let issues: Vec<String> = issues.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            issues,
+            vec!["_doc/blog/doc_manifest.json: Error in widget \"markdown\": No such widget"]
+        );
+    }
+
+    #[test]
+    fn fail_without_listing() {
+        #[derive(Debug)]
+        struct ReadOnly;
+        impl Storage for ReadOnly {
+            fn read(&self, path: &Path) -> LocalBoxFuture<'static, Result<Bytes, FetchError>> {
+                future::err(FetchError::StorageError(
+                    path.to_string(),
+                    "unreadable".into(),
+                ))
+                .boxed_local()
+            }
+        }
+        match block_on(check_site(&Registry::builtin(), Rc::new(ReadOnly))) {
+            Err(FetchError::StorageError(url, message)) => {
+                assert_eq!(url, "_doc");
+                assert_eq!(message, "storage can not list documents");
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+}
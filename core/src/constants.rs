@@ -4,3 +4,23 @@ pub const DOC_MANIFEST_FILE: &str = "doc_manifest.json";
 pub const SITE_MANIFEST_FILE: &str = "site_manifest.json";
 
 pub const MAX_WIDGET_RECURSION: usize = 64;
+
+/// Longest chain of redirects followed.
+pub const MAX_REDIRECTS: usize = 16;
+
+/// Unreachable documents kept loaded by default.
+pub const DEFAULT_CACHE_SIZE: usize = 16;
+
+/// Attribute of the root element that holds pre-rendered page.
+pub const PRERENDERED_ATTR: &str = "data-prerendered";
+
+/// Attribute of pre-rendered head elements added by widgets.
+pub const HEAD_ATTR: &str = "data-semka-head";
+
+/// Attributes of `<title>` and `<html>` keeping the title and language
+/// of the page template, shown for pages without own ones.
+pub const DEFAULT_TITLE_ATTR: &str = "data-semka-default-title";
+pub const DEFAULT_LANG_ATTR: &str = "data-semka-default-lang";
+
+/// Attribute of the root element of every document view.
+pub const DOC_PATH_ATTR: &str = "data-doc-path";
@@ -1,15 +1,41 @@
 use crate::manifests::SiteManifest;
 use crate::path::Path;
+use crate::storage::Storage;
 use seed::Url;
+use std::collections::BTreeMap;
+use std::rc::Rc;
 
 mod registry;
 pub use registry::Registry;
 
+/// Where page paths are kept in URLs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Routing {
+    /// `/base/page/path`, needs the server to serve the app for every path.
+    Path,
+    /// `/base/#/page/path`, works on any static host.
+    Hash,
+}
+
+impl Default for Routing {
+    fn default() -> Self {
+        Self::Path
+    }
+}
+
 #[derive(Debug)]
 pub struct Context {
     pub url: Url,
     pub page_path: Path,
+    /// Query parameters of the page URL, values of a key in order of appearance.
+    pub query: BTreeMap<String, Vec<String>>,
+    /// Fragment of the page URL without `#`.
+    pub fragment: Option<String>,
     pub base_path: Path,
+    pub routing: Routing,
     pub site_manifest: SiteManifest,
     pub registry: Registry,
+    pub storage: Rc<dyn Storage>,
+    /// How many documents not reachable from the current page are kept loaded.
+    pub cache_size: usize,
 }
@@ -1,42 +1,192 @@
+use crate::builtin_widgets;
 use crate::error::WidgetError;
-use crate::widget::WidgetFactory;
-use std::collections::BTreeMap;
+use crate::widget::{WidgetFactory, WidgetName};
+use itertools::Itertools;
+use std::collections::{BTreeMap, BTreeSet};
 
 #[derive(Debug)]
 pub struct Registry {
     factories: Vec<Box<dyn WidgetFactory>>,
     factory_by_widget: BTreeMap<&'static str, usize>,
+    versioned: Vec<(WidgetName, usize)>,
+    warnings: Vec<String>,
 }
 
 impl Registry {
-    pub(crate) fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             factories: vec![],
             factory_by_widget: BTreeMap::new(),
+            versioned: vec![],
+            warnings: vec![],
         }
     }
-    pub(crate) fn add_widget<F>(mut self, factory: F) -> Self
+    /// Registry with built-in widgets only.
+    pub fn builtin() -> Self {
+        Self::new().add_widget(builtin_widgets::LoadingFactory::new())
+    }
+    pub fn add_widget<F>(mut self, factory: F) -> Self
     where
         F: WidgetFactory + 'static,
     {
         let index = self.factories.len();
-        factory.can_handle().iter().for_each(|widget| {
-            self.factory_by_widget.insert(widget, index);
-        });
         self.factories.push(Box::new(factory));
+        let widgets: BTreeSet<&'static str> =
+            self.factories[index].can_handle().iter().copied().collect();
+        for widget in widgets {
+            let name = WidgetName::parse(widget);
+            if let Some(prev_index) = self.factory_by_widget.insert(widget, index) {
+                self.warnings.push(format!(
+                    "Widget \"{}\" is now handled by {:?} instead of {:?}",
+                    widget, self.factories[index], self.factories[prev_index]
+                ));
+                self.versioned
+                    .retain(|(versioned_name, _)| Some(versioned_name) != name.as_ref());
+            }
+            if let Some(name) = name {
+                self.versioned.push((name, index));
+            }
+        }
         self
     }
 
+    /// Problems found while registering widgets, e.g. duplicate registrations.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Resolves versioned widget to the newest compatible version,
+    /// other widgets by exact name.
     pub fn get_widget(&self, widget: &str) -> Result<&dyn WidgetFactory, WidgetError> {
-        let index = self
-            .factory_by_widget
-            .get(widget)
-            .copied()
-            .ok_or(WidgetError::new(widget, "No such widget"))?;
+        let index = match self.factory_by_widget.get(widget) {
+            Some(index) if WidgetName::parse(widget).is_none() => *index,
+            _ => self.resolve_version(widget)?,
+        };
         let factory = self
             .factories
             .get(index)
             .ok_or(WidgetError::new(widget, "Unexpected factory index"))?;
         Ok(factory.as_ref())
     }
+
+    fn resolve_version(&self, widget: &str) -> Result<usize, WidgetError> {
+        let requested =
+            WidgetName::parse(widget).ok_or_else(|| WidgetError::new(widget, "No such widget"))?;
+        let (compatible, incompatible): (Vec<_>, Vec<_>) = self
+            .versioned
+            .iter()
+            .filter(|(name, _)| name.is_same_widget(&requested))
+            .partition(|(name, _)| name.is_compatible_with(&requested));
+        if let Some((_, index)) = compatible.into_iter().max_by_key(|(name, _)| &name.version) {
+            Ok(*index)
+        } else if !incompatible.is_empty() {
+            Err(WidgetError::new(
+                widget,
+                format!(
+                    "No compatible version, available: {}",
+                    incompatible.iter().map(|(name, _)| name).join(", ")
+                ),
+            ))
+        } else {
+            Err(WidgetError::new(widget, "No such widget"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifests::DocManifest;
+    use crate::path::Path;
+    use crate::widget::Widget;
+
+    // Factory identified by the names it handles.
+    #[derive(Debug)]
+    struct Factory(&'static [&'static str]);
+
+    impl WidgetFactory for Factory {
+        fn can_handle(&self) -> &'static [&'static str] {
+            self.0
+        }
+        fn create(&self, _: Path, _: Path, _: DocManifest) -> Result<Box<dyn Widget>, WidgetError> {
+            Err(WidgetError::new(self.0[0], "Not for tests"))
+        }
+    }
+
+    fn registry() -> Registry {
+        Registry::new()
+            .add_widget(Factory(&["semka-0.1-markdown"]))
+            .add_widget(Factory(&["semka-0.2.1-markdown", "markdown"]))
+            .add_widget(Factory(&["semka-1.3-markdown"]))
+            .add_widget(Factory(&["semka-2.0-markdown"]))
+            .add_widget(Factory(&["other-1.4-markdown"]))
+    }
+
+    fn resolve(registry: &Registry, widget: &str) -> Result<&'static str, String> {
+        registry
+            .get_widget(widget)
+            .map(|factory| factory.can_handle()[0])
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn resolve_compatible_version() {
+        let registry = registry();
+        assert_eq!(
+            resolve(&registry, "semka-0.1-markdown"),
+            Ok("semka-0.1-markdown")
+        );
+        assert_eq!(
+            resolve(&registry, "semka-0.2-markdown"),
+            Ok("semka-0.2.1-markdown")
+        );
+        assert_eq!(
+            resolve(&registry, "semka-1-markdown"),
+            Ok("semka-1.3-markdown")
+        );
+        assert_eq!(
+            resolve(&registry, "semka-1.2.5-markdown"),
+            Ok("semka-1.3-markdown")
+        );
+        assert_eq!(
+            resolve(&registry, "other-1.0-markdown"),
+            Ok("other-1.4-markdown")
+        );
+        // Names without version are matched exactly.
+        assert_eq!(resolve(&registry, "markdown"), Ok("semka-0.2.1-markdown"));
+    }
+
+    #[test]
+    fn reject_incompatible_version() {
+        let registry = registry();
+        let err = resolve(&registry, "semka-1.4-markdown").unwrap_err();
+        assert!(err.contains("No compatible version"), "{}", err);
+        assert!(err.contains("semka-1.3-markdown"), "{}", err);
+        let err = resolve(&registry, "semka-0.3-markdown").unwrap_err();
+        assert!(err.contains("No compatible version"), "{}", err);
+        assert!(resolve(&registry, "semka-1.0-blog")
+            .unwrap_err()
+            .contains("No such widget"));
+        assert!(resolve(&registry, "blog")
+            .unwrap_err()
+            .contains("No such widget"));
+    }
+
+    #[test]
+    fn replace_registered_version() {
+        let registry = registry().add_widget(Factory(&["semka-1.3-markdown", "semka-1.5-md"]));
+        assert_eq!(registry.warnings().len(), 1);
+        assert_eq!(
+            resolve(&registry, "semka-1.1-markdown"),
+            Ok("semka-1.3-markdown")
+        );
+        assert_eq!(
+            registry
+                .get_widget("semka-1.3-markdown")
+                .unwrap()
+                .can_handle()
+                .len(),
+            2
+        );
+    }
 }
@@ -2,7 +2,7 @@ use failure::Fail;
 
 #[derive(Fail, Debug, Clone)]
 #[fail(display = "Can not parse path: {}", _0)]
-pub struct ParsePathError(&'static str);
+pub struct ParsePathError(pub(crate) &'static str);
 
 #[derive(Fail, Debug, Clone)]
 #[fail(display = "Path error: {}", _0)]
@@ -21,6 +21,10 @@ pub enum FetchError {
     /// Request construction failed.
     #[fail(display = "Can not fetch \"{}\". Request error {}", _0, _1)]
     RequestError(String, String),
+    #[fail(display = "Can not fetch \"{}\". Decode error {}", _0, _1)]
+    DecodeError(String, String),
+    #[fail(display = "Can not fetch \"{}\". Storage error {}", _0, _1)]
+    StorageError(String, String),
     #[fail(display = "Can not fetch \"{}\". Not found {} {}", url, code, text)]
     NotFound {
         url: String,
@@ -100,20 +104,27 @@ impl FetchError {
             _ => false,
         }
     }
+    /// Errors that may go away if the request is repeated.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            FetchError::NetworkError(..) | FetchError::ServerError { .. }
+        )
+    }
 }
 
 #[derive(Fail, Debug, Clone)]
 #[fail(display = "Error in widget \"{}\": {}", widget, err)]
 pub struct WidgetError {
     widget: String,
-    err: &'static str,
+    err: String,
 }
 
 impl WidgetError {
-    pub fn new(widget: impl AsRef<str>, err: &'static str) -> Self {
+    pub fn new(widget: impl AsRef<str>, err: impl ToString) -> Self {
         Self {
             widget: widget.as_ref().to_string(),
-            err,
+            err: err.to_string(),
         }
     }
 }
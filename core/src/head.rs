@@ -0,0 +1,313 @@
+//! Document head of the browser page.
+
+use crate::constants::{DEFAULT_LANG_ATTR, DEFAULT_TITLE_ATTR, HEAD_ATTR};
+use crate::html;
+use crate::manifests::PageMeta;
+use crate::node_ext::to_absolute_url;
+use crate::path::Path;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Document, Element};
+
+/// Shows page metadata in the document head.
+/// Title and language of the page template are restored if the page has none,
+/// `<meta>` tags without content are removed.
+pub fn apply_page_meta(meta: &PageMeta) -> Result<(), JsValue> {
+    let document = seed::document();
+    let head = match document.head() {
+        Some(head) => head,
+        None => return Ok(()),
+    };
+    let title = match head.query_selector("title")? {
+        Some(title) => title,
+        None => {
+            let title = document.create_element("title")?;
+            head.append_child(&title)?;
+            title
+        }
+    };
+    let default_title = template_value(&title, DEFAULT_TITLE_ATTR, title.text_content())?;
+    document.set_title(meta.title.as_ref().unwrap_or(&default_title));
+    if let Some(html) = document.document_element() {
+        let default_lang = template_value(&html, DEFAULT_LANG_ATTR, html.get_attribute("lang"))?;
+        match meta.lang.as_ref().unwrap_or(&default_lang) {
+            lang if lang.is_empty() => html.remove_attribute("lang")?,
+            lang => html.set_attribute("lang", lang)?,
+        }
+    }
+    for (key_attr, key, content) in meta.meta_tags() {
+        let existing = head.query_selector(&format!("meta[{}=\"{}\"]", key_attr, key))?;
+        match (existing, content) {
+            (Some(el), Some(content)) => el.set_attribute("content", &content)?,
+            (Some(el), None) => el.remove(),
+            (None, Some(content)) => {
+                let el = document.create_element("meta")?;
+                el.set_attribute(key_attr, key)?;
+                el.set_attribute("content", &content)?;
+                head.append_child(&el)?;
+            }
+            (None, None) => (),
+        }
+    }
+    Ok(())
+}
+
+// Value of the page template kept in `attr` of the element.
+// It is the `current` value until the page metadata is shown first time.
+fn template_value(el: &Element, attr: &str, current: Option<String>) -> Result<String, JsValue> {
+    if let Some(value) = el.get_attribute(attr) {
+        return Ok(value);
+    }
+    let value = current.unwrap_or_default();
+    el.set_attribute(attr, &value)?;
+    Ok(value)
+}
+
+/// Points `<base>` of the document to `base_path`,
+/// so relative URLs are resolved against the site root.
+pub fn set_base(base_path: &Path) -> Result<(), JsValue> {
+    let document = seed::document();
+    let head = match document.head() {
+        Some(head) => head,
+        None => return Ok(()),
+    };
+    let base = match head.query_selector("base")? {
+        Some(base) => base,
+        None => {
+            let base = document.create_element("base")?;
+            head.insert_before(&base, head.first_child().as_ref())?;
+            base
+        }
+    };
+    base.set_attribute(
+        "href",
+        &format!("{}/", base_path.to_string().trim_end_matches('/')),
+    )
+}
+
+/// Element a widget puts into the document head.
+/// Relative URLs are resolved against the site base path.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HeadElement {
+    Link {
+        rel: String,
+        href: String,
+    },
+    Meta {
+        name: String,
+        content: String,
+    },
+    Style(String),
+    /// `<script type="module">` loaded from the URL.
+    ModuleScript(String),
+}
+
+impl HeadElement {
+    pub fn stylesheet(href: impl ToString) -> Self {
+        Self::Link {
+            rel: "stylesheet".to_string(),
+            href: href.to_string(),
+        }
+    }
+
+    fn tag(&self) -> &'static str {
+        match self {
+            Self::Link { .. } => "link",
+            Self::Meta { .. } => "meta",
+            Self::Style(_) => "style",
+            Self::ModuleScript(_) => "script",
+        }
+    }
+
+    fn attrs(&self, base_path: &Path) -> Vec<(&'static str, String)> {
+        match self {
+            Self::Link { rel, href } => vec![
+                ("rel", rel.clone()),
+                ("href", to_absolute_url(href, base_path)),
+            ],
+            Self::Meta { name, content } => {
+                vec![("name", name.clone()), ("content", content.clone())]
+            }
+            Self::Style(_) => vec![],
+            Self::ModuleScript(src) => vec![
+                ("type", "module".to_string()),
+                ("src", to_absolute_url(src, base_path)),
+            ],
+        }
+    }
+
+    fn text(&self) -> Option<&str> {
+        match self {
+            Self::Style(css) => Some(css),
+            _ => None,
+        }
+    }
+
+    /// Serializes the element for pre-rendered pages.
+    /// Browse mode adopts such elements instead of adding them again.
+    pub fn to_html(&self, base_path: &Path) -> String {
+        let mut attrs = String::new();
+        for (name, value) in self.attrs(base_path) {
+            write!(attrs, " {}=\"{}\"", name, html::escape(&value, true)).unwrap();
+        }
+        match self {
+            Self::Link { .. } | Self::Meta { .. } => {
+                format!("<{}{} {} />", self.tag(), attrs, HEAD_ATTR)
+            }
+            _ => format!(
+                "<{tag}{} {}>{}</{tag}>",
+                attrs,
+                HEAD_ATTR,
+                self.text().unwrap_or(""),
+                tag = self.tag()
+            ),
+        }
+    }
+
+    fn create(&self, document: &Document, base_path: &Path) -> Result<Element, JsValue> {
+        let el = document.create_element(self.tag())?;
+        for (name, value) in self.attrs(base_path) {
+            el.set_attribute(name, &value)?;
+        }
+        if let Some(text) = self.text() {
+            el.set_text_content(Some(text));
+        }
+        Ok(el)
+    }
+
+    fn matches(&self, el: &Element, base_path: &Path) -> bool {
+        el.tag_name().eq_ignore_ascii_case(self.tag())
+            && self
+                .attrs(base_path)
+                .iter()
+                .all(|(name, value)| el.get_attribute(name).as_ref() == Some(value))
+            && match self.text() {
+                Some(text) => el.text_content().as_deref() == Some(text),
+                None => true,
+            }
+    }
+}
+
+/// Elements added to the document head on behalf of widgets.
+///
+/// Every element is added once however many documents own it,
+/// and removed when the last of them releases it.
+#[derive(Debug, Default)]
+pub struct HeadElements {
+    owned: BTreeMap<Path, BTreeSet<HeadElement>>,
+    // Added elements with the number of documents owning them.
+    added: BTreeMap<HeadElement, (Element, usize)>,
+    // Unused pre-rendered elements are removed once the page is loaded.
+    prerendered_checked: bool,
+}
+
+impl HeadElements {
+    /// Documents owning elements.
+    pub fn owners(&self) -> impl Iterator<Item = &Path> {
+        self.owned.keys()
+    }
+
+    /// Makes `elements` the elements of the `owner` document.
+    /// Elements it no longer owns are released.
+    pub fn set(
+        &mut self,
+        owner: &Path,
+        elements: &BTreeSet<HeadElement>,
+        base_path: &Path,
+    ) -> Result<(), JsValue> {
+        if self.owned.get(owner) == Some(elements) {
+            return Ok(());
+        }
+        let previous = self.owned.remove(owner).unwrap_or_default();
+        for element in elements.difference(&previous) {
+            self.acquire(element, base_path)?;
+        }
+        for element in previous.difference(elements) {
+            self.release_element(element);
+        }
+        if !elements.is_empty() {
+            self.owned.insert(owner.clone(), elements.clone());
+        }
+        Ok(())
+    }
+
+    /// Releases all elements of the `owner` document.
+    pub fn release(&mut self, owner: &Path) {
+        for element in self.owned.remove(owner).unwrap_or_default() {
+            self.release_element(&element);
+        }
+    }
+
+    /// Removes pre-rendered elements no document owns.
+    /// Should be called once the page is loaded, later calls do nothing.
+    pub fn remove_prerendered(&mut self) -> Result<(), JsValue> {
+        if self.prerendered_checked {
+            return Ok(());
+        }
+        self.prerendered_checked = true;
+        for el in prerendered_elements()? {
+            if !self
+                .added
+                .values()
+                .any(|(added, _)| added.is_same_node(Some(&el)))
+            {
+                el.remove();
+            }
+        }
+        Ok(())
+    }
+
+    fn acquire(&mut self, element: &HeadElement, base_path: &Path) -> Result<(), JsValue> {
+        if let Some((_, count)) = self.added.get_mut(element) {
+            *count += 1;
+            return Ok(());
+        }
+        let document = seed::document();
+        let head = match document.head() {
+            Some(head) => head,
+            None => return Ok(()),
+        };
+        let prerendered = if self.prerendered_checked {
+            None
+        } else {
+            prerendered_elements()?
+                .into_iter()
+                .find(|el| element.matches(el, base_path))
+        };
+        let el = match prerendered {
+            Some(el) => el,
+            None => {
+                let el = element.create(&document, base_path)?;
+                head.append_child(&el)?;
+                el
+            }
+        };
+        self.added.insert(element.clone(), (el, 1));
+        Ok(())
+    }
+
+    fn release_element(&mut self, element: &HeadElement) {
+        if let Some((_, count)) = self.added.get_mut(element) {
+            *count -= 1;
+            if *count == 0 {
+                if let Some((el, _)) = self.added.remove(element) {
+                    el.remove();
+                }
+            }
+        }
+    }
+}
+
+// Elements of the pre-rendered page put into the head by `HeadElement::to_html`.
+fn prerendered_elements() -> Result<Vec<Element>, JsValue> {
+    let head = match seed::document().head() {
+        Some(head) => head,
+        None => return Ok(vec![]),
+    };
+    let nodes = head.query_selector_all(&format!("[{}]", HEAD_ATTR))?;
+    Ok((0..nodes.length())
+        .filter_map(|idx| nodes.item(idx))
+        .filter_map(|node| node.dyn_into::<Element>().ok())
+        .collect())
+}
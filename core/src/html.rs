@@ -0,0 +1,335 @@
+//! Building and serializing HTML without browser DOM.
+//!
+//! In the browser `raw` and `markdown` use Seed's DOM based parsers.
+//! Natively Markdown is converted to nodes directly and raw HTML is kept
+//! as is, so widgets can be rendered to HTML by the pre-render step.
+
+use seed::prelude::*;
+
+/// Tag of the element that holds raw HTML in native builds.
+const RAW_TAG: &str = "semka-raw";
+
+const VOID_TAGS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Same as `raw!` but also works natively.
+pub fn raw<Ms>(html: &str) -> Vec<Node<Ms>> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        Node::from_html(html)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut el = El::empty(Tag::from(RAW_TAG));
+        el.add_text(html.to_string());
+        vec![Node::Element(el)]
+    }
+}
+
+/// Same as `md!` but also works natively.
+pub fn markdown<Ms>(text: &str) -> Vec<Node<Ms>> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        Node::from_markdown(text)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        markdown_to_nodes(text)
+    }
+}
+
+/// Serializes nodes to HTML. Event handlers are dropped.
+pub fn to_html<Ms>(nodes: &[Node<Ms>]) -> String {
+    let mut html = String::new();
+    nodes.iter().for_each(|node| write_node(node, &mut html));
+    html
+}
+
+fn write_node<Ms>(node: &Node<Ms>, html: &mut String) {
+    match node {
+        Node::Element(el) if el.tag.as_str() == RAW_TAG => {
+            el.children.iter().for_each(|child| match child {
+                Node::Text(text) => html.push_str(&text.text),
+                child => write_node(child, html),
+            });
+        }
+        Node::Element(el) if el.tag == Tag::Placeholder => {
+            el.children.iter().for_each(|child| write_node(child, html));
+        }
+        Node::Element(el) => {
+            let tag = el.tag.as_str();
+            html.push('<');
+            html.push_str(tag);
+            for (key, value) in el.attrs.vals.iter() {
+                if *key == At::Style && !el.style.vals.is_empty() {
+                    continue;
+                }
+                match value {
+                    AtValue::Ignored => (),
+                    AtValue::None => {
+                        html.push(' ');
+                        html.push_str(key.as_str());
+                    }
+                    AtValue::Some(value) => {
+                        html.push_str(&format!(" {}=\"{}\"", key.as_str(), escape(value, true)));
+                    }
+                }
+            }
+            if !el.style.vals.is_empty() {
+                html.push_str(&format!(
+                    " style=\"{}\"",
+                    escape(&el.style.to_string(), true)
+                ));
+            }
+            html.push('>');
+            if VOID_TAGS.contains(&tag) {
+                return;
+            }
+            el.children.iter().for_each(|child| write_node(child, html));
+            html.push_str("</");
+            html.push_str(tag);
+            html.push('>');
+        }
+        Node::Text(text) => html.push_str(&escape(&text.text, false)),
+        Node::Empty => (),
+    }
+}
+
+/// Escapes text for HTML, also quotes if `in_attr`.
+pub fn escape(text: &str, in_attr: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' if in_attr => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Builds the same tree as `pulldown_cmark::html::push_html` would produce.
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+fn markdown_to_nodes<Ms>(text: &str) -> Vec<Node<Ms>> {
+    use pulldown_cmark::{Alignment, CodeBlockKind, Event, Options, Parser, Tag as MdTag};
+    use std::collections::HashMap;
+
+    struct Builder<Ms> {
+        stack: Vec<El<Ms>>,
+        nodes: Vec<Node<Ms>>,
+    }
+
+    impl<Ms> Builder<Ms> {
+        fn open(&mut self, tag: &str) -> &mut El<Ms> {
+            self.stack.push(El::empty(Tag::from(tag.to_string())));
+            self.stack.last_mut().unwrap()
+        }
+        fn close(&mut self) {
+            if let Some(el) = self.stack.pop() {
+                self.add(Node::Element(el));
+            }
+        }
+        fn add(&mut self, node: Node<Ms>) {
+            match self.stack.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => self.nodes.push(node),
+            }
+        }
+        fn add_text(&mut self, text: &str) {
+            self.add(Node::new_text(text.to_string()));
+        }
+    }
+
+    let mut builder = Builder {
+        stack: vec![],
+        nodes: vec![],
+    };
+    let mut footnotes: HashMap<String, usize> = HashMap::new();
+    let mut footnote_number = |label: &str| {
+        let next = footnotes.len() + 1;
+        *footnotes.entry(label.to_string()).or_insert(next)
+    };
+    let mut alignments: Vec<Alignment> = vec![];
+    let mut in_table_head = false;
+    let mut in_table_body = false;
+    let mut cell_index = 0;
+
+    for event in Parser::new_ext(text, Options::all()) {
+        match event {
+            Event::Start(tag) => match tag {
+                MdTag::Paragraph => {
+                    builder.open("p");
+                }
+                MdTag::Heading(level) => {
+                    builder.open(&format!("h{}", level));
+                }
+                MdTag::BlockQuote => {
+                    builder.open("blockquote");
+                }
+                MdTag::CodeBlock(kind) => {
+                    builder.open("pre");
+                    let code = builder.open("code");
+                    if let CodeBlockKind::Fenced(lang) = kind {
+                        if let Some(lang) = lang.split(' ').next().filter(|l| !l.is_empty()) {
+                            code.add_class(format!("language-{}", lang));
+                        }
+                    }
+                }
+                MdTag::List(Some(start)) => {
+                    let list = builder.open("ol");
+                    if start != 1 {
+                        list.add_attr("start", start);
+                    }
+                }
+                MdTag::List(None) => {
+                    builder.open("ul");
+                }
+                MdTag::Item => {
+                    builder.open("li");
+                }
+                MdTag::FootnoteDefinition(label) => {
+                    let number = footnote_number(&label);
+                    let definition = builder.open("div");
+                    definition.add_class("footnote-definition");
+                    definition.add_attr("id", label.to_string());
+                    let sup = builder.open("sup");
+                    sup.add_class("footnote-definition-label");
+                    sup.add_text(number.to_string());
+                    builder.close();
+                }
+                MdTag::Table(table_alignments) => {
+                    alignments = table_alignments;
+                    builder.open("table");
+                }
+                MdTag::TableHead => {
+                    in_table_head = true;
+                    cell_index = 0;
+                    builder.open("thead");
+                    builder.open("tr");
+                }
+                MdTag::TableRow => {
+                    if !in_table_body {
+                        in_table_body = true;
+                        builder.open("tbody");
+                    }
+                    cell_index = 0;
+                    builder.open("tr");
+                }
+                MdTag::TableCell => {
+                    let cell = builder.open(if in_table_head { "th" } else { "td" });
+                    match alignments.get(cell_index) {
+                        Some(Alignment::Left) => cell.add_attr("align", "left"),
+                        Some(Alignment::Center) => cell.add_attr("align", "center"),
+                        Some(Alignment::Right) => cell.add_attr("align", "right"),
+                        _ => cell,
+                    };
+                    cell_index += 1;
+                }
+                MdTag::Emphasis => {
+                    builder.open("em");
+                }
+                MdTag::Strong => {
+                    builder.open("strong");
+                }
+                MdTag::Strikethrough => {
+                    builder.open("del");
+                }
+                MdTag::Link(_, url, title) => {
+                    let link = builder.open("a");
+                    link.add_attr("href", url.to_string());
+                    if !title.is_empty() {
+                        link.add_attr("title", title.to_string());
+                    }
+                }
+                MdTag::Image(_, url, title) => {
+                    let image = builder.open("img");
+                    image.add_attr("src", url.to_string());
+                    if !title.is_empty() {
+                        image.add_attr("title", title.to_string());
+                    }
+                }
+            },
+            Event::End(tag) => match tag {
+                MdTag::CodeBlock(_) => {
+                    builder.close();
+                    builder.close();
+                }
+                MdTag::TableHead => {
+                    in_table_head = false;
+                    builder.close();
+                    builder.close();
+                }
+                MdTag::Table(_) => {
+                    if in_table_body {
+                        in_table_body = false;
+                        builder.close();
+                    }
+                    builder.close();
+                }
+                MdTag::Image(..) => {
+                    // Text inside of the image is its alternative text.
+                    if let Some(image) = builder.stack.last_mut() {
+                        let alt: String = std::mem::take(&mut image.children)
+                            .iter()
+                            .map(text_content)
+                            .collect();
+                        image.add_attr("alt", alt);
+                    }
+                    builder.close();
+                }
+                _ => builder.close(),
+            },
+            Event::Text(text) => builder.add_text(&text),
+            Event::Code(text) => {
+                builder.open("code").add_text(text.to_string());
+                builder.close();
+            }
+            Event::Html(html) => raw(&html).into_iter().for_each(|node| builder.add(node)),
+            Event::FootnoteReference(label) => {
+                let number = footnote_number(&label);
+                builder.open("sup").add_class("footnote-reference");
+                builder
+                    .open("a")
+                    .add_attr("href", format!("#{}", label))
+                    .add_text(number.to_string());
+                builder.close();
+                builder.close();
+            }
+            Event::SoftBreak => builder.add_text("\n"),
+            Event::HardBreak => {
+                builder.open("br");
+                builder.close();
+            }
+            Event::Rule => {
+                builder.open("hr");
+                builder.close();
+            }
+            Event::TaskListMarker(checked) => {
+                let input = builder.open("input");
+                input.add_attr("disabled", AtValue::None);
+                input.add_attr("type", "checkbox");
+                if checked {
+                    input.add_attr("checked", AtValue::None);
+                }
+                builder.close();
+            }
+        }
+    }
+    while !builder.stack.is_empty() {
+        builder.close();
+    }
+    builder.nodes
+}
+
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+fn text_content<Ms>(node: &Node<Ms>) -> String {
+    match node {
+        Node::Element(el) => el.children.iter().map(text_content).collect(),
+        Node::Text(text) => text.text.to_string(),
+        Node::Empty => String::new(),
+    }
+}
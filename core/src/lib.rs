@@ -1,7 +1,14 @@
 // (Lines like the one below ignore selected Clippy rules
 //  - it's useful when you want to check your code with `cargo make verify`
 // but some rules are too "annoying" or are not applicable for your case.)
-#![allow(clippy::wildcard_imports)]
+#![allow(
+    clippy::wildcard_imports,
+    // Widget constructors return `Box<dyn Widget>`.
+    clippy::new_ret_no_self,
+    clippy::new_without_default,
+    clippy::redundant_static_lifetimes,
+    clippy::enum_variant_names
+)]
 
 #[macro_use]
 extern crate derivative;
@@ -14,23 +21,32 @@ static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
 pub mod app;
 mod builtin_widgets;
+pub mod check;
 pub mod constants;
 pub mod context;
 pub mod error;
+pub mod head;
+pub mod html;
 pub mod manifests;
 pub mod node_ext;
+pub mod notification;
 pub mod path;
+pub mod prerender;
+pub mod storage;
 pub mod utils;
 pub mod widget;
 
 pub mod prelude {
     pub use super::app::Launcher;
     pub use super::constants::*;
-    pub use super::context::Context;
+    pub use super::context::{Context, Routing};
     pub use super::error::*;
+    pub use super::head::HeadElement;
     pub use super::manifests::*;
     pub use super::node_ext::NodeExt;
+    pub use super::notification::{Notification, Severity};
     pub use super::path::Path;
+    pub use super::storage::Storage;
     pub use super::utils::*;
     pub use super::widget::*;
     pub use failure::Error;
@@ -1,5 +1,8 @@
+use crate::constants::MAX_REDIRECTS;
+use crate::error::WidgetError;
 use crate::path::Path;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -8,17 +11,394 @@ pub struct SiteManifest {
     pub index_page: Path,
     #[serde(default)]
     pub master_page: Path,
+    #[serde(default)]
+    pub not_found_page: Path,
+    /// Master pages of site sections, the first matching route wins.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub routes: Vec<Route>,
+    /// Old paths of moved pages.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub redirects: BTreeMap<Path, Redirect>,
+    /// Defaults for pages without own metadata.
+    #[serde(flatten)]
+    pub meta: SiteMeta,
+}
+
+impl SiteManifest {
+    /// Master page of the first route matching `page_path` or the default one.
+    pub fn master_page_for(&self, page_path: &Path) -> &Path {
+        self.routes
+            .iter()
+            .find(|route| route.matches(page_path))
+            .map_or(&self.master_page, |route| &route.master_page)
+    }
+
+    /// Default and route master pages.
+    pub fn master_pages(&self) -> Vec<&Path> {
+        std::iter::once(&self.master_page)
+            .chain(self.routes.iter().map(|route| &route.master_page))
+            .filter(|page| !page.is_empty())
+            .collect()
+    }
+
+    /// Where `page_path` is redirected to, following chained redirects.
+    /// Pages under a redirected path are moved under its target.
+    /// The chain is permanent only if every redirect in it is.
+    /// `None` if the page is not redirected or redirects loop.
+    pub fn redirect(&self, page_path: &Path) -> Option<Redirect> {
+        let mut redirect: Option<Redirect> = None;
+        let mut target = page_path.clone();
+        for _ in 0..MAX_REDIRECTS {
+            let found = self
+                .redirects
+                .iter()
+                .filter(|(from, _)| target == **from || target.is_subpath(from))
+                .max_by_key(|(from, _)| from.len());
+            match found {
+                Some((from, next)) => {
+                    target = next.to.join(&target.releative_to(from).ok()?);
+                    redirect = Some(Redirect {
+                        to: target.clone(),
+                        permanent: next.permanent && redirect.iter().all(|r| r.permanent),
+                    });
+                }
+                None => return redirect,
+            }
+        }
+        None
+    }
+
+    /// Every page the manifest refers to.
+    pub fn pages(&self) -> Vec<&Path> {
+        let mut pages = vec![&self.index_page, &self.not_found_page];
+        pages.extend(self.master_pages());
+        pages.extend(self.redirects.values().map(|redirect| &redirect.to));
+        pages
+    }
+
+    /// Makes page paths relative to the site root, the same as paths of pages in URLs.
+    pub fn normalized(self) -> Self {
+        fn to_releative(path: Path) -> Path {
+            if path.is_absolute() {
+                path.releative_to(&Path::new_absolute()).unwrap()
+            } else {
+                path
+            }
+        }
+        Self {
+            index_page: to_releative(self.index_page),
+            master_page: to_releative(self.master_page),
+            not_found_page: to_releative(self.not_found_page),
+            routes: self
+                .routes
+                .into_iter()
+                .map(|route| Route {
+                    master_page: to_releative(route.master_page),
+                    ..route
+                })
+                .collect(),
+            redirects: self
+                .redirects
+                .into_iter()
+                .map(|(from, redirect)| {
+                    let redirect = Redirect {
+                        to: to_releative(redirect.to),
+                        ..redirect
+                    };
+                    (to_releative(from), redirect)
+                })
+                .collect(),
+            ..self
+        }
+    }
+
+    pub fn pages_mut(&mut self) -> Vec<&mut Path> {
+        let mut pages = vec![
+            &mut self.index_page,
+            &mut self.master_page,
+            &mut self.not_found_page,
+        ];
+        pages.extend(self.routes.iter_mut().map(|route| &mut route.master_page));
+        pages.extend(self.redirects.values_mut().map(|redirect| &mut redirect.to));
+        pages
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Redirect {
+    pub to: Path,
+    /// Temporary redirects are not meant to be remembered by search engines.
+    #[serde(default = "default_permanent")]
+    pub permanent: bool,
+}
+
+fn default_permanent() -> bool {
+    true
+}
+
+/// Master page for a site section.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Route {
+    /// Path prefix of the section. Segments may use `*` to match
+    /// any part of a single segment and `**` to match any number of segments.
+    pub path: String,
+    /// Pages of the section are shown without master page if empty.
+    #[serde(default)]
+    pub master_page: Path,
+}
+
+impl Route {
+    /// Whether `page_path` is within the section.
+    pub fn matches(&self, page_path: &Path) -> bool {
+        let pattern: Vec<&str> = self.path.split('/').filter(|s| !s.is_empty()).collect();
+        let page: Vec<&str> = page_path.iter().collect();
+        match_prefix(&pattern, &page)
+    }
+}
+
+fn match_prefix(pattern: &[&str], page: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => true,
+        Some((&"**", rest)) => (0..=page.len()).any(|skip| match_prefix(rest, &page[skip..])),
+        Some((segment, rest)) => match page.split_first() {
+            Some((part, page_rest)) => {
+                match_segment(segment, part) && match_prefix(rest, page_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn match_segment(pattern: &str, segment: &str) -> bool {
+    match pattern.find('*') {
+        None => pattern == segment,
+        Some(star) => {
+            let (prefix, rest) = (&pattern[..star], &pattern[star + 1..]);
+            segment.starts_with(prefix)
+                && segment
+                    .char_indices()
+                    .map(|(idx, _)| idx)
+                    .chain(std::iter::once(segment.len()))
+                    .filter(|idx| *idx >= prefix.len())
+                    .any(|idx| match_segment(rest, &segment[idx..]))
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DocManifest {
     pub widget: String,
+    /// Widget specific parameters.
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub params: serde_json::Map<String, serde_json::Value>,
+    #[serde(flatten)]
+    pub meta: PageMeta,
+}
+
+impl DocManifest {
+    pub fn parse_params<T>(&self) -> Result<T, WidgetError>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        serde_json::from_value(serde_json::Value::Object(self.params.clone()))
+            .map_err(|err| WidgetError::new(&self.widget, format!("Invalid parameters: {}", err)))
+    }
 }
 
 impl Default for DocManifest {
     fn default() -> Self {
         Self {
             widget: "empty".to_string(),
+            params: serde_json::Map::new(),
+            meta: PageMeta::default(),
+        }
+    }
+}
+
+/// Site-wide defaults of the page metadata.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SiteMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Language tag, e.g. "en-US".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    /// Open Graph image URL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+/// Metadata of the page shown in the document head.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PageMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Language tag, e.g. "en-US".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    /// Open Graph image URL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// Publication date, e.g. "2020-07-01".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Page is not ready to be published.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub draft: bool,
+}
+
+impl PageMeta {
+    /// Fills missing fields from `defaults`.
+    pub fn or(self, defaults: &PageMeta) -> Self {
+        Self {
+            title: self.title.or_else(|| defaults.title.clone()),
+            description: self.description.or_else(|| defaults.description.clone()),
+            lang: self.lang.or_else(|| defaults.lang.clone()),
+            image: self.image.or_else(|| defaults.image.clone()),
+            author: self.author.or_else(|| defaults.author.clone()),
+            date: self.date.or_else(|| defaults.date.clone()),
+            tags: if self.tags.is_empty() {
+                defaults.tags.clone()
+            } else {
+                self.tags
+            },
+            draft: self.draft || defaults.draft,
+        }
+    }
+
+    /// Fills missing fields from site-wide `defaults`.
+    pub fn or_site(self, defaults: &SiteMeta) -> Self {
+        Self {
+            title: self.title.or_else(|| defaults.title.clone()),
+            description: self.description.or_else(|| defaults.description.clone()),
+            lang: self.lang.or_else(|| defaults.lang.clone()),
+            image: self.image.or_else(|| defaults.image.clone()),
+            ..self
+        }
+    }
+
+    /// Content of every `<meta>` tag managed by Semka, `None` if the tag should be absent.
+    /// Items are the key attribute, its value and the content.
+    pub fn meta_tags(&self) -> Vec<(&'static str, &'static str, Option<String>)> {
+        vec![
+            ("name", "description", self.description.clone()),
+            ("name", "author", self.author.clone()),
+            (
+                "name",
+                "keywords",
+                Some(self.tags.join(", ")).filter(|tags| !tags.is_empty()),
+            ),
+            ("property", "og:title", self.title.clone()),
+            ("property", "og:description", self.description.clone()),
+            ("property", "og:image", self.image.clone()),
+            (
+                "property",
+                "og:locale",
+                self.lang.as_ref().map(|lang| lang.replace('-', "_")),
+            ),
+            ("property", "article:published_time", self.date.clone()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(path: &str) -> Path {
+        path.parse().unwrap()
+    }
+
+    fn route(pattern: &str) -> Route {
+        Route {
+            path: pattern.to_string(),
+            master_page: Path::new(),
         }
     }
+
+    fn site_manifest(json: &str) -> SiteManifest {
+        serde_json::from_str::<SiteManifest>(json)
+            .unwrap()
+            .normalized()
+    }
+
+    #[test]
+    fn route_matches_prefix() {
+        assert!(route("blog").matches(&path("blog")));
+        assert!(route("/blog/").matches(&path("blog/2020/post")));
+        assert!(!route("blog").matches(&path("blogs")));
+        assert!(!route("blog/2020").matches(&path("blog")));
+        assert!(route("").matches(&path("any/page")));
+    }
+
+    #[test]
+    fn route_matches_wildcards() {
+        assert!(route("blog/*/post").matches(&path("blog/2020/post")));
+        assert!(route("blog/20*").matches(&path("blog/2020")));
+        assert!(!route("blog/20*").matches(&path("blog/1999")));
+        assert!(route("docs/**/api").matches(&path("docs/api")));
+        assert!(route("docs/**/api").matches(&path("docs/v1/rust/api/index")));
+        assert!(!route("docs/**/api").matches(&path("docs/v1/rust")));
+    }
+
+    #[test]
+    fn first_matching_route_wins() {
+        let manifest = site_manifest(
+            r#"{
+                "masterPage": "master",
+                "routes": [
+                    {"path": "blog/drafts", "masterPage": ""},
+                    {"path": "blog", "masterPage": "/blog-master"}
+                ]
+            }"#,
+        );
+        assert_eq!(
+            manifest.master_page_for(&path("blog/post")),
+            &path("blog-master")
+        );
+        assert!(manifest.master_page_for(&path("blog/drafts/x")).is_empty());
+        assert_eq!(manifest.master_page_for(&path("about")), &path("master"));
+    }
+
+    #[test]
+    fn redirect_chains() {
+        let manifest = site_manifest(
+            r#"{
+                "redirects": {
+                    "/old": {"to": "/new"},
+                    "older": {"to": "old/page", "permanent": false},
+                    "new/moved": {"to": "final"}
+                }
+            }"#,
+        );
+        let redirect = manifest.redirect(&path("old")).unwrap();
+        assert_eq!(redirect.to, path("new"));
+        assert!(redirect.permanent);
+        let redirect = manifest.redirect(&path("old/sub/page")).unwrap();
+        assert_eq!(redirect.to, path("new/sub/page"));
+        let redirect = manifest.redirect(&path("older")).unwrap();
+        assert_eq!(redirect.to, path("new/page"));
+        assert!(!redirect.permanent);
+        let redirect = manifest.redirect(&path("old/moved")).unwrap();
+        assert_eq!(redirect.to, path("final"));
+        assert!(manifest.redirect(&path("other")).is_none());
+    }
+
+    #[test]
+    fn redirect_loop() {
+        let manifest = site_manifest(
+            r#"{"redirects": {"a": {"to": "b"}, "b": {"to": "a"}, "c": {"to": "a"}}}"#,
+        );
+        assert!(manifest.redirect(&path("a")).is_none());
+        assert!(manifest.redirect(&path("c")).is_none());
+    }
 }
@@ -1,3 +1,5 @@
+use crate::constants::{DOC_DIR, DOC_PATH_ATTR};
+use crate::context::{Context, Routing};
 use crate::path::Path;
 use crate::utils::is_url_absolute;
 use seed::prelude::*;
@@ -46,18 +48,204 @@ impl<Ms: 'static> NodeExt<Node<Ms>> for Node<Ms> {
     }
 }
 
-pub(crate) fn to_absolute_href<Ms>(node: Node<Ms>, base_path: &Path) -> Node<Ms> {
+/// Makes URLs of the element and its children absolute.
+/// Relative page links are resolved against `document`, other relative URLs,
+/// like `src` of images, against the document directory.
+/// Subtrees of other documents are skipped, they are resolved on their own.
+pub(crate) fn resolve_urls<Ms>(node: Node<Ms>, document: &Path, ctx: &Context) -> Node<Ms> {
     match node {
         Node::Element(mut el) => {
-            if let Some(AtValue::Some(href)) = el.attrs.vals.get_mut(&At::Href) {
-                if !is_url_absolute(&href) {
-                    if let Ok(path) = href.parse::<Path>() {
-                        *href = base_path.join(&path).to_string();
-                    }
+            let is_link = el.tag == Tag::A;
+            for (at, value) in el.attrs.vals.iter_mut() {
+                if let AtValue::Some(url) = value {
+                    *url = match at {
+                        At::Href if is_link => resolve_link(url, document, ctx),
+                        At::Href | At::Src | At::Poster => resolve_file_url(url, document, ctx),
+                        At::SrcSet => split_srcset(url)
+                            .into_iter()
+                            .map(|(url, descriptor)| {
+                                let url = resolve_file_url(url, document, ctx);
+                                match descriptor {
+                                    "" => url,
+                                    descriptor => format!("{} {}", url, descriptor),
+                                }
+                            })
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        _ => continue,
+                    };
                 }
             }
+            let doc_path_attr = At::from(DOC_PATH_ATTR);
+            el.children = el
+                .children
+                .into_iter()
+                .map(|child| match child {
+                    Node::Element(ref child_el)
+                        if child_el.attrs.vals.contains_key(&doc_path_attr) =>
+                    {
+                        child
+                    }
+                    child => resolve_urls(child, document, ctx),
+                })
+                .collect();
             Node::Element(el)
         }
         node => node,
     }
 }
+
+/// Resolves `href` of a link to the page, relative paths are siblings of `document`.
+fn resolve_link(href: &str, document: &Path, ctx: &Context) -> String {
+    let (path, suffix) = split_url_suffix(href);
+    match path.parse::<Path>() {
+        // Query or fragment of the current page.
+        _ if path.is_empty() && !suffix.is_empty() => format!(
+            "{}{}",
+            page_url(&ctx.page_path, &ctx.base_path, ctx.routing),
+            suffix
+        ),
+        Ok(path) if !is_url_absolute(href) => {
+            let dir: Path = document
+                .iter()
+                .take(document.len().saturating_sub(1))
+                .collect();
+            format!(
+                "{}{}",
+                page_url(&site_path(&dir, &path), &ctx.base_path, ctx.routing),
+                suffix
+            )
+        }
+        _ => href.to_string(),
+    }
+}
+
+/// Resolves URL of a file, relative paths are in the directory of `document`.
+fn resolve_file_url(url: &str, document: &Path, ctx: &Context) -> String {
+    let (path, suffix) = split_url_suffix(url);
+    match path.parse::<Path>() {
+        Ok(path) if !is_url_absolute(url) => {
+            let dir = if document.is_empty() {
+                Path::new()
+            } else {
+                Path::new().add(DOC_DIR).join(document)
+            };
+            format!("{}{}", ctx.base_path.join(&site_path(&dir, &path)), suffix)
+        }
+        _ => url.to_string(),
+    }
+}
+
+/// Splits `srcset` into URLs and descriptors of its image candidates.
+/// Commas end a candidate only after its URL, so `data:` URLs are kept whole.
+fn split_srcset(srcset: &str) -> Vec<(&str, &str)> {
+    let mut candidates = Vec::new();
+    let mut rest = srcset;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        if rest.is_empty() {
+            return candidates;
+        }
+        let url_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let url = &rest[..url_end];
+        if url.ends_with(',') {
+            candidates.push((url.trim_end_matches(','), ""));
+            rest = &rest[url_end..];
+            continue;
+        }
+        let descriptor_end = rest[url_end..]
+            .find(',')
+            .map_or(rest.len(), |pos| url_end + pos);
+        candidates.push((url, rest[url_end..descriptor_end].trim()));
+        rest = &rest[descriptor_end..];
+    }
+}
+
+/// Joins `path` to `dir`, the result never leaves the site root.
+fn site_path(dir: &Path, path: &Path) -> Path {
+    Path::new_absolute()
+        .join(dir)
+        .join(path)
+        .normalize()
+        .iter()
+        .collect()
+}
+
+/// Splits URL into the path and the query with the fragment.
+fn split_url_suffix(url: &str) -> (&str, &str) {
+    url.split_at(url.find(&['?', '#'][..]).unwrap_or(url.len()))
+}
+
+/// URL of the page at `page_path`. Files of documents are always linked by path.
+pub(crate) fn page_url(page_path: &Path, base_path: &Path, routing: Routing) -> String {
+    match routing {
+        Routing::Hash if page_path.iter().next() != Some(DOC_DIR) => format!(
+            "{}/#/{}",
+            base_path.to_string().trim_end_matches('/'),
+            page_path
+        ),
+        _ => base_path.join(page_path).to_string(),
+    }
+}
+
+/// Makes relative `url` absolute by prepending `base_path`.
+pub(crate) fn to_absolute_url(url: &str, base_path: &Path) -> String {
+    Some(url)
+        .filter(|url| !is_url_absolute(url))
+        .and_then(|url| url.parse::<Path>().ok())
+        .map(|path| base_path.join(&path).to_string())
+        .unwrap_or_else(|| url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_urls() {
+        let base_path: Path = "/site".parse().unwrap();
+        let page: Path = "blog/post".parse().unwrap();
+        assert_eq!(
+            page_url(&page, &base_path, Routing::Path),
+            "/site/blog/post"
+        );
+        assert_eq!(
+            page_url(&page, &base_path, Routing::Hash),
+            "/site/#/blog/post"
+        );
+        assert_eq!(
+            page_url(&page, &Path::new_absolute(), Routing::Hash),
+            "/#/blog/post"
+        );
+        assert_eq!(
+            page_url(&Path::new(), &base_path, Routing::Hash),
+            "/site/#/"
+        );
+        // Document files are served by path with hash routing too.
+        let file: Path = "_doc/blog/image.png".parse().unwrap();
+        assert_eq!(
+            page_url(&file, &base_path, Routing::Hash),
+            "/site/_doc/blog/image.png"
+        );
+    }
+
+    #[test]
+    fn split_srcset_candidates() {
+        assert_eq!(
+            split_srcset("a.png, b.png 2x,c.png 300w"),
+            vec![("a.png", ""), ("b.png", "2x"), ("c.png", "300w")]
+        );
+        assert_eq!(
+            split_srcset(" a.png, b.png, "),
+            vec![("a.png", ""), ("b.png", "")]
+        );
+    }
+
+    #[test]
+    fn split_srcset_data_urls() {
+        assert_eq!(
+            split_srcset("data:image/png;base64,AAAA 1x, b.png 2x"),
+            vec![("data:image/png;base64,AAAA", "1x"), ("b.png", "2x")]
+        );
+    }
+}
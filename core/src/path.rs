@@ -1,8 +1,26 @@
 use crate::error::{ParsePathError, PathError};
 use itertools::{EitherOrBoth, Itertools};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
 use std::fmt::Write;
 use std::rc::Rc;
 
+const DIR_UP: &str = "..";
+const DIR_CURRENT: &str = ".";
+
+/// Characters escaped in parts of URL paths.
+const PART_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
 #[derive(Clone, Eq, PartialEq)]
 struct PathInner {
     all_parts: String,
@@ -51,7 +69,7 @@ impl Path {
     }
     pub fn iter(&self) -> PathIterator {
         PathIterator {
-            inner: &*self.0,
+            inner: &self.0,
             forward_i: 0,
             back_i: self.len(),
         }
@@ -94,6 +112,69 @@ impl Path {
             _ => Err(PathError("Can not find releative path")),
         }
     }
+    /// Resolves `.` and `..` parts without looking at the storage.
+    /// Leading `..` are kept in relative paths and dropped in absolute ones.
+    pub fn normalize(&self) -> Self {
+        let mut parts: Vec<&str> = vec![];
+        for part in self.iter() {
+            match part {
+                DIR_CURRENT => (),
+                DIR_UP if !parts.is_empty() && parts.last() != Some(&DIR_UP) => {
+                    parts.pop();
+                }
+                DIR_UP if self.is_absolute() => (),
+                part => parts.push(part),
+            }
+        }
+        let path: Path = parts.into_iter().collect();
+        if self.is_absolute() {
+            Path::new_absolute().join(&path)
+        } else {
+            path
+        }
+    }
+    /// Joins relative `other` the same way as `join`,
+    /// but fails if the result is outside of `self`.
+    pub fn join_confined(&self, other: &Self) -> Result<Self, PathError> {
+        let other = other.normalize();
+        if other.is_absolute() || other.iter().next() == Some(DIR_UP) {
+            Err(PathError("Path is outside of the directory"))
+        } else {
+            Ok(self.join(&other))
+        }
+    }
+    /// Parses URL path with percent-encoded parts.
+    pub fn from_url_path(url_path: &str) -> Result<Self, ParsePathError> {
+        let path: Path = url_path
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .map(|part| match percent_decode_str(part).decode_utf8() {
+                // Encoded slash would split the part when the path is formatted again.
+                Ok(part) if part.contains('/') => Err(ParsePathError("Encoded slash in path")),
+                Ok(part) => Ok(part),
+                Err(_) => Err(ParsePathError("Percent-encoded part is not UTF-8")),
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .collect();
+        Ok(if url_path.starts_with('/') {
+            Path::new_absolute().join(&path)
+        } else {
+            path
+        })
+    }
+    /// Formats the path with percent-encoded parts to use in URLs.
+    pub fn to_url_path(&self) -> String {
+        let parts = self
+            .iter()
+            .map(|part| utf8_percent_encode(part, PART_ENCODE_SET))
+            .format("/");
+        if self.is_absolute() {
+            format!("/{}", parts)
+        } else {
+            parts.to_string()
+        }
+    }
     pub fn is_subpath(&self, rhs: &Self) -> bool {
         match (self.is_absolute(), rhs.is_absolute()) {
             (true, true) | (false, false) => is_subpath(self.iter(), rhs.iter()),
@@ -268,7 +349,6 @@ where
     B: std::iter::Iterator<Item = &'a str>,
     P: std::iter::Iterator<Item = &'a str>,
 {
-    static DIR_UP: &str = "..";
     let (up, down): (Vec<&'a str>, Vec<&'a str>) =
         base.zip_longest(path)
             .fold((vec![], vec![]), |mut parts, pp| {
@@ -284,7 +364,7 @@ where
                 };
                 parts
             });
-    up.into_iter().chain(down.into_iter())
+    up.into_iter().chain(down)
 }
 
 pub fn is_subpath<'a, I>(lhs: I, rhs: I) -> bool
@@ -304,3 +384,62 @@ where
     }
     false // lhs == rhs
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(s: &str) -> Path {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn normalize_relative() {
+        assert_eq!(path("a/./b/../c").normalize(), path("a/c"));
+        assert_eq!(path("../a").normalize(), path("../a"));
+        assert_eq!(path("a/../..").normalize(), path(".."));
+        assert_eq!(path("a/../../../b").normalize(), path("../../b"));
+        assert_eq!(path("./.").normalize(), Path::new());
+    }
+
+    #[test]
+    fn normalize_absolute() {
+        assert_eq!(path("/../a").normalize(), path("/a"));
+        assert_eq!(path("/a/../..").normalize(), Path::new_absolute());
+        assert_eq!(path("/a/./b/..").normalize(), path("/a"));
+    }
+
+    #[test]
+    fn join_confined() {
+        let dir = path("_doc/a");
+        assert_eq!(
+            dir.join_confined(&path("b/./c")).unwrap(),
+            path("_doc/a/b/c")
+        );
+        assert_eq!(dir.join_confined(&path("b/..")).unwrap(), dir);
+        assert!(dir.join_confined(&path("..")).is_err());
+        assert!(dir.join_confined(&path("../b")).is_err());
+        assert!(dir.join_confined(&path("b/../..")).is_err());
+        assert!(dir.join_confined(&path("./../a/b")).is_err());
+        assert!(dir.join_confined(&path("/b")).is_err());
+    }
+
+    #[test]
+    fn from_url_path() {
+        assert_eq!(
+            Path::from_url_path("/a%20b/%C3%A9").unwrap(),
+            path("/a b/\u{e9}")
+        );
+        assert_eq!(Path::from_url_path("a//b/").unwrap(), path("a/b"));
+        assert!(Path::from_url_path("/a%2Fb").is_err());
+        assert!(Path::from_url_path("/..%2F..").is_err());
+        assert!(Path::from_url_path("/a%FF").is_err());
+    }
+
+    #[test]
+    fn url_path_round_trip() {
+        let p = path("/a b/c#d?e/100%");
+        assert_eq!(p.to_url_path(), "/a%20b/c%23d%3Fe/100%25");
+        assert_eq!(Path::from_url_path(&p.to_url_path()).unwrap(), p);
+    }
+}
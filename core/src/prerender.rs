@@ -0,0 +1,439 @@
+//! Renders pages to HTML without browser.
+//!
+//! Documents are loaded and widgets are driven the same way as in
+//! browse mode, but every command is awaited in place. The result is
+//! the same tree `Widget::view` produces, serialized with `html::to_html`.
+
+use crate::builtin_widgets;
+use crate::context::{Context, Registry, Routing};
+use crate::head::HeadElement;
+use crate::html;
+use crate::manifests::{PageMeta, SiteManifest};
+use crate::notification::Notification;
+use crate::path::Path;
+use crate::storage::Storage;
+use crate::utils;
+use crate::widget::driver::{self, Order};
+use crate::widget::{find_cycles, Dependencies, Widget, WidgetMsg, WidgetOrders};
+use failure::Error;
+use seed::Url;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::rc::Rc;
+
+/// Pre-rendered page.
+#[derive(Debug)]
+pub struct Page {
+    pub html: String,
+    /// Metadata for the document head, site defaults included.
+    pub meta: PageMeta,
+    /// Head elements requested by widgets, serialized.
+    pub head: Vec<String>,
+    /// Notifications widgets sent while loading, e.g. failures.
+    pub notifications: Vec<Notification>,
+}
+
+enum Task {
+    Load(Path),
+    Init(Path),
+    Update(Path, WidgetMsg),
+}
+
+#[derive(Default)]
+struct Renderer {
+    widgets: BTreeMap<Path, Box<dyn Widget>>,
+    documents: BTreeMap<Path, Path>,
+    dependencies: BTreeMap<Path, BTreeSet<Path>>,
+    meta: BTreeMap<Path, PageMeta>,
+    head: BTreeMap<Path, BTreeSet<HeadElement>>,
+    tasks: VecDeque<Task>,
+    notifications: Vec<Notification>,
+}
+
+/// Renders pages of a site loaded from `storage`.
+pub struct Prerenderer {
+    ctx: Context,
+}
+
+impl Prerenderer {
+    pub fn new<S>(site_manifest: SiteManifest, registry: Registry, storage: S) -> Self
+    where
+        S: Storage + 'static,
+    {
+        Self {
+            ctx: Context {
+                url: Url::new(),
+                page_path: Path::new(),
+                query: BTreeMap::new(),
+                fragment: None,
+                base_path: Path::new_absolute(),
+                routing: Routing::default(),
+                site_manifest,
+                registry,
+                storage: Rc::new(storage),
+                cache_size: 0,
+            },
+        }
+    }
+
+    /// Absolute path the site is served from. Links are made relative to it.
+    pub fn base_path(self, base_path: Path) -> Self {
+        Self {
+            ctx: Context {
+                base_path,
+                ..self.ctx
+            },
+        }
+    }
+
+    /// Style of links to pages, should be the same as of the app.
+    pub fn routing(self, routing: Routing) -> Self {
+        Self {
+            ctx: Context {
+                routing,
+                ..self.ctx
+            },
+        }
+    }
+
+    /// Renders `page_path` (or the index page if empty) within the master page.
+    pub async fn render(&mut self, page_path: &Path) -> Page {
+        self.ctx.url = Url::new().set_path(self.ctx.base_path.join(page_path).iter());
+        self.ctx.page_path = page_path.clone();
+        render_page(&self.ctx).await
+    }
+}
+
+async fn render_page(ctx: &Context) -> Page {
+    let page_path = if ctx.page_path.is_empty() {
+        ctx.site_manifest.index_page.clone()
+    } else {
+        ctx.page_path.clone()
+    };
+    let full_path = ctx
+        .site_manifest
+        .master_page_for(&page_path)
+        .join(&page_path);
+
+    let mut renderer = Renderer::default();
+    renderer.tasks.push_back(Task::Load(full_path.clone()));
+    while let Some(task) = renderer.tasks.pop_front() {
+        match task {
+            Task::Load(path) if renderer.widgets.contains_key(&path) => (),
+            Task::Load(path) => renderer.load_document(path, ctx).await,
+            Task::Init(path) => {
+                let result = match renderer.widgets.get_mut(&path) {
+                    Some(widget) => widget.init(&path, ctx),
+                    None => Ok(None),
+                };
+                renderer.handle_widget_result(result, path, ctx).await;
+            }
+            Task::Update(path, msg) => {
+                let result = match renderer.widgets.get_mut(&path) {
+                    Some(widget) => widget.update(msg, ctx),
+                    None => Ok(None),
+                };
+                renderer.handle_widget_result(result, path, ctx).await;
+            }
+        }
+    }
+
+    let cycles = find_cycles(&renderer.dependencies, &full_path);
+    let node = Dependencies::new(
+        &renderer.widgets,
+        &renderer.documents,
+        &renderer.dependencies,
+        &cycles,
+        &renderer.meta,
+        ctx,
+    )
+    .view(&full_path);
+    let head: BTreeSet<&HeadElement> = renderer.head.values().flatten().collect();
+    Page {
+        html: html::to_html(&[node]),
+        meta: renderer
+            .meta
+            .remove(&page_path)
+            .unwrap_or_default()
+            .or_site(&ctx.site_manifest.meta),
+        head: head
+            .into_iter()
+            .map(|element| element.to_html(&ctx.base_path))
+            .collect(),
+        notifications: renderer.notifications,
+    }
+}
+
+impl Renderer {
+    async fn load_document(&mut self, path: Path, ctx: &Context) {
+        let found =
+            utils::find_doc_manifest(ctx.storage.clone(), path.clone(), &ctx.site_manifest).await;
+        if let Ok((document, manifest)) = &found {
+            self.documents.insert(path.clone(), document.clone());
+            self.meta.insert(path.clone(), manifest.meta.clone());
+        }
+        self.dependencies.remove(&path);
+        match driver::create_widget(&path, found, ctx) {
+            Ok(widget) => {
+                self.widgets.insert(path.clone(), widget);
+            }
+            Err(err) => self.fail(path.clone(), err),
+        }
+        self.tasks.push_back(Task::Init(path));
+    }
+
+    async fn handle_widget_result(
+        &mut self,
+        result: Result<Option<WidgetOrders>, Error>,
+        path: Path,
+        ctx: &Context,
+    ) {
+        match result {
+            Ok(Some(w_orders)) => self.perform_widget_orders(w_orders, path, ctx).await,
+            Ok(None) => (),
+            Err(err) => {
+                self.fail(path.clone(), err);
+                self.tasks.push_back(Task::Init(path));
+            }
+        }
+    }
+
+    async fn perform_widget_orders(
+        &mut self,
+        w_orders: WidgetOrders,
+        doc_path: Path,
+        ctx: &Context,
+    ) {
+        let document = self
+            .documents
+            .get(&doc_path)
+            .cloned()
+            .unwrap_or_else(|| doc_path.clone());
+        for order in driver::interpret_orders(w_orders, &document, ctx) {
+            match order {
+                Order::Perform(fut) => {
+                    let msg = fut.await;
+                    self.tasks.push_back(Task::Update(doc_path.clone(), msg));
+                }
+                Order::Notify(notification) => self.notifications.push(notification),
+                Order::UpdateDependencies(dependencies) => {
+                    for dep in dependencies.iter() {
+                        self.tasks.push_back(Task::Load(dep.clone()));
+                    }
+                    self.dependencies.insert(doc_path.clone(), dependencies);
+                }
+                Order::UpdateHead(elements) => {
+                    self.head.insert(doc_path.clone(), elements);
+                }
+                Order::UpdateMeta(meta) => {
+                    self.meta.insert(doc_path.clone(), meta);
+                }
+                // Nothing changes between attempts here.
+                Order::Reload | Order::Skip => (),
+                Order::Fail(err) => {
+                    self.fail(doc_path.clone(), err);
+                    self.tasks.push_back(Task::Init(doc_path.clone()));
+                }
+            }
+        }
+    }
+
+    fn fail(&mut self, path: Path, err: Error) {
+        self.dependencies.remove(&path);
+        self.head.remove(&path);
+        self.widgets
+            .insert(path.clone(), builtin_widgets::Failed::new(path, &err));
+        self.notifications.push(Notification::error(err));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::WidgetError;
+    use crate::manifests::DocManifest;
+    use crate::storage::MemoryStorage;
+    use crate::widget::{Dependencies, WidgetFactory};
+    use futures::executor::block_on;
+    use seed::{prelude::*, *};
+    use serde::Deserialize;
+
+    // Shows a text file of its document followed by included documents.
+    // Master pages include the page they are shown for.
+    #[derive(Debug)]
+    struct TextWidget {
+        file: Path,
+        includes: Vec<Path>,
+        text: String,
+    }
+
+    impl Widget for TextWidget {
+        fn init(&mut self, _path: &Path, _ctx: &Context) -> Result<Option<WidgetOrders>, Error> {
+            Ok(Some(
+                WidgetOrders::new()
+                    .update_deps(self.includes.iter().cloned().collect())
+                    .fetch_text(self.file.clone()),
+            ))
+        }
+        fn update(
+            &mut self,
+            msg: WidgetMsg,
+            _ctx: &Context,
+        ) -> Result<Option<WidgetOrders>, Error> {
+            if let WidgetMsg::FetchTextResult(_, result) = msg {
+                self.text = result?;
+            }
+            Ok(None)
+        }
+        fn view<'a>(
+            &'a self,
+            dependencies: Dependencies<'a>,
+            _ctx: &'a Context,
+        ) -> Node<WidgetMsg> {
+            div![
+                p![&self.text],
+                self.includes.iter().map(|path| dependencies.view(path))
+            ]
+        }
+        fn widget_name(&self) -> &'static str {
+            "text"
+        }
+    }
+
+    #[derive(Debug)]
+    struct TextFactory;
+
+    #[derive(Deserialize)]
+    struct Params {
+        file: Path,
+        #[serde(default)]
+        include: Vec<Path>,
+    }
+
+    impl WidgetFactory for TextFactory {
+        fn can_handle(&self) -> &'static [&'static str] {
+            &["text"]
+        }
+        fn create(
+            &self,
+            _document: Path,
+            sub_path: Path,
+            manifest: DocManifest,
+        ) -> Result<Box<dyn Widget>, WidgetError> {
+            let params: Params = manifest.parse_params()?;
+            let mut includes = params.include;
+            if !sub_path.is_empty() {
+                includes.push(sub_path);
+            }
+            Ok(Box::new(TextWidget {
+                file: params.file,
+                includes,
+                text: String::new(),
+            }))
+        }
+    }
+
+    fn prerenderer() -> Prerenderer {
+        prerenderer_for(r#"{"indexPage": "hello", "masterPage": "master", "title": "Site"}"#)
+    }
+
+    fn prerenderer_for(site_manifest: &str) -> Prerenderer {
+        let storage = MemoryStorage::new()
+            .file(
+                "_doc/master/doc_manifest.json",
+                r#"{"widget": "text", "params": {"file": "text.txt"}}"#,
+            )
+            .file("_doc/master/text.txt", "Master text")
+            .file(
+                "_doc/hello/doc_manifest.json",
+                r#"{"widget": "text", "title": "Hello", "params": {"file": "text.txt"}}"#,
+            )
+            .file("_doc/hello/text.txt", "Hello text")
+            .file(
+                "_doc/hello/nested/doc_manifest.json",
+                r#"{"widget": "text", "params": {"file": "text.txt"}}"#,
+            )
+            .file("_doc/hello/nested/text.txt", "Nested text")
+            .file(
+                "_doc/world/doc_manifest.json",
+                r#"{"widget": "text", "params": {"file": "text.txt"}}"#,
+            )
+            .file("_doc/world/text.txt", "World text")
+            .file(
+                "_doc/not_found/doc_manifest.json",
+                r#"{"widget": "text", "params": {"file": "text.txt"}}"#,
+            )
+            .file("_doc/not_found/text.txt", "Not found text")
+            .file(
+                "_doc/escape/doc_manifest.json",
+                r#"{"widget": "text", "params": {"file": "../hello/text.txt"}}"#,
+            );
+        let site_manifest: SiteManifest = serde_json::from_str(site_manifest).unwrap();
+        let registry = Registry::builtin().add_widget(TextFactory);
+        Prerenderer::new(site_manifest.normalized(), registry, storage)
+    }
+
+    #[test]
+    fn render_page_within_master_page() {
+        let page = block_on(prerenderer().render(&Path::new()));
+        assert!(page.notifications.is_empty(), "{:?}", page.notifications);
+        let master = page.html.find("Master text").unwrap();
+        let hello = page.html.find("Hello text").unwrap();
+        assert!(master < hello);
+        assert_eq!(page.meta.title.as_deref(), Some("Hello"));
+    }
+
+    #[test]
+    fn render_missing_page() {
+        let page = block_on(prerenderer().render(&"missing".parse().unwrap()));
+        assert!(page.html.contains("Master text"));
+        assert!(!page.html.contains("Hello text"));
+        assert_eq!(page.meta.title.as_deref(), Some("Site"));
+    }
+
+    #[test]
+    fn render_not_found_page() {
+        let page = block_on(
+            prerenderer_for(r#"{"masterPage": "master", "notFoundPage": "not_found"}"#)
+                .render(&"missing".parse().unwrap()),
+        );
+        assert!(page.notifications.is_empty(), "{:?}", page.notifications);
+        let master = page.html.find("Master text").unwrap();
+        let not_found = page.html.find("Not found text").unwrap();
+        assert!(master < not_found);
+        assert!(!page.html.contains("Document: missing"));
+    }
+
+    #[test]
+    fn render_builtin_not_found() {
+        let page = block_on(prerenderer().render(&"missing".parse().unwrap()));
+        assert!(page.html.contains("Document: missing"));
+        assert!(!page.html.contains("Not found text"));
+    }
+
+    #[test]
+    fn render_nested_document() {
+        let page = block_on(prerenderer().render(&"hello/nested".parse().unwrap()));
+        assert!(page.notifications.is_empty(), "{:?}", page.notifications);
+        assert!(page.html.contains("Nested text"));
+        assert!(!page.html.contains("Hello text"));
+    }
+
+    #[test]
+    fn pass_sub_path_to_widget() {
+        // The widget of "hello" includes its sub-path as a document.
+        let page = block_on(prerenderer().render(&"hello/world".parse().unwrap()));
+        assert!(page.notifications.is_empty(), "{:?}", page.notifications);
+        let hello = page.html.find("Hello text").unwrap();
+        let world = page.html.find("World text").unwrap();
+        assert!(hello < world);
+    }
+
+    #[test]
+    fn fail_widget_reading_outside_of_document() {
+        let page = block_on(prerenderer().render(&"escape".parse().unwrap()));
+        assert_eq!(page.notifications.len(), 1);
+        assert!(page.notifications[0].message.contains("outside"));
+        assert!(page.html.contains("Master text"));
+        assert!(!page.html.contains("Hello text"));
+    }
+}
@@ -0,0 +1,166 @@
+use crate::error::FetchError;
+use crate::path::Path;
+use bytes::Bytes;
+use futures::future::{self, FutureExt, LocalBoxFuture};
+
+mod http;
+mod local;
+mod memory;
+mod retry;
+
+pub use http::HttpStorage;
+pub use local::LocalStorageOverlay;
+pub use memory::MemoryStorage;
+pub use retry::{RetryPolicy, RetryStorage};
+
+/// Source of site files.
+///
+/// Paths are relative to the site root, e.g. `_doc/index/text.md`.
+pub trait Storage: std::fmt::Debug {
+    fn read(&self, path: &Path) -> LocalBoxFuture<'static, Result<Bytes, FetchError>>;
+
+    fn can_write(&self) -> bool {
+        false
+    }
+    fn write(&self, path: &Path, _data: Bytes) -> LocalBoxFuture<'static, Result<(), FetchError>> {
+        unsupported(path, "write")
+    }
+
+    fn can_list(&self) -> bool {
+        false
+    }
+    /// Lists all files below `path`. Returned paths are relative to `path`.
+    fn list(&self, path: &Path) -> LocalBoxFuture<'static, Result<Vec<Path>, FetchError>> {
+        unsupported(path, "list")
+    }
+}
+
+pub fn read_text(
+    storage: &dyn Storage,
+    path: &Path,
+) -> impl std::future::Future<Output = Result<String, FetchError>> + 'static {
+    let url = path.to_string();
+    storage.read(path).map(move |result| {
+        result.and_then(|bytes| {
+            String::from_utf8(bytes.to_vec())
+                .map_err(|err| FetchError::DecodeError(url, err.to_string()))
+        })
+    })
+}
+
+pub fn read_json<T>(
+    storage: &dyn Storage,
+    path: &Path,
+) -> impl std::future::Future<Output = Result<T, FetchError>> + 'static
+where
+    T: serde::de::DeserializeOwned + 'static,
+{
+    let url = path.to_string();
+    storage.read(path).map(move |result| {
+        result.and_then(|bytes| {
+            serde_json::from_slice(&bytes)
+                .map_err(|err| FetchError::SerdeError(url, err.to_string()))
+        })
+    })
+}
+
+fn unsupported<T: 'static>(
+    path: &Path,
+    operation: &str,
+) -> LocalBoxFuture<'static, Result<T, FetchError>> {
+    future::err(FetchError::StorageError(
+        path.to_string(),
+        format!("{} is not supported", operation),
+    ))
+    .boxed_local()
+}
+
+fn not_found(path: &Path) -> FetchError {
+    FetchError::ClientError {
+        url: path.to_string(),
+        code: 404,
+        text: "Not Found".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+    use futures::executor::block_on;
+
+    fn path(path: &str) -> Path {
+        path.parse().unwrap()
+    }
+
+    fn site() -> MemoryStorage {
+        MemoryStorage::new()
+            .file("site_manifest.json", r#"{"indexPage": "blog"}"#)
+            .file("_doc/blog/doc_manifest.json", r#"{"widget": "markdown"}"#)
+            .file("_doc/blog/text.md", "# Blog")
+            .file("_doc/blog/broken.json", "{")
+            .file("_doc/blog/binary", vec![0xffu8, 0xfe])
+    }
+
+    #[test]
+    fn read_site_without_server() {
+        let storage = site();
+        let site_manifest = block_on(utils::fetch_site_manifest(&storage)).unwrap();
+        assert_eq!(site_manifest.index_page, path("blog"));
+        let manifest = block_on(utils::fetch_doc_manifest(&storage, &path("blog"))).unwrap();
+        assert_eq!(manifest.widget, "markdown");
+        let text = block_on(read_text(&storage, &path("_doc/blog/text.md"))).unwrap();
+        assert_eq!(text, "# Blog");
+    }
+
+    #[test]
+    fn read_errors() {
+        let storage = site();
+        let missing = block_on(storage.read(&path("_doc/blog/missing.md"))).unwrap_err();
+        assert!(missing.is_not_found());
+        match block_on(read_json::<serde_json::Value>(
+            &storage,
+            &path("_doc/blog/broken.json"),
+        )) {
+            Err(FetchError::SerdeError(url, _)) => assert_eq!(url, "_doc/blog/broken.json"),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        match block_on(read_text(&storage, &path("_doc/blog/binary"))) {
+            Err(FetchError::DecodeError(url, _)) => assert_eq!(url, "_doc/blog/binary"),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn write_and_list() {
+        let storage = site();
+        assert!(storage.can_write() && storage.can_list());
+        block_on(storage.write(&path("_doc/new/text.md"), Bytes::from("New"))).unwrap();
+        let text = block_on(read_text(&storage, &path("_doc/new/text.md"))).unwrap();
+        assert_eq!(text, "New");
+        let files = block_on(storage.list(&path("_doc/blog"))).unwrap();
+        let expected: Vec<Path> = ["binary", "broken.json", "doc_manifest.json", "text.md"]
+            .iter()
+            .map(|file| path(file))
+            .collect();
+        assert_eq!(files, expected);
+    }
+
+    #[test]
+    fn unsupported_operations() {
+        #[derive(Debug)]
+        struct ReadOnly;
+        impl Storage for ReadOnly {
+            fn read(&self, path: &Path) -> LocalBoxFuture<'static, Result<Bytes, FetchError>> {
+                future::err(not_found(path)).boxed_local()
+            }
+        }
+        assert!(!ReadOnly.can_write() && !ReadOnly.can_list());
+        match block_on(ReadOnly.list(&Path::new())) {
+            Err(FetchError::StorageError(_, message)) => {
+                assert_eq!(message, "list is not supported")
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+}
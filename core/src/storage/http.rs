@@ -0,0 +1,39 @@
+use crate::error::FetchError;
+use crate::path::Path;
+use crate::storage::Storage;
+use crate::utils;
+use bytes::Bytes;
+use futures::future::{FutureExt, LocalBoxFuture};
+
+/// Read-only storage that fetches files from the web server.
+#[derive(Debug, Clone, Default)]
+pub struct HttpStorage {
+    base_url: String,
+}
+
+impl HttpStorage {
+    /// Files are requested relative to the document base URL.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn base_url(self, base_url: impl AsRef<str>) -> Self {
+        Self {
+            base_url: base_url.as_ref().trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn url(&self, path: &Path) -> String {
+        if self.base_url.is_empty() {
+            path.to_url_path()
+        } else {
+            format!("{}/{}", self.base_url, path.to_url_path())
+        }
+    }
+}
+
+impl Storage for HttpStorage {
+    fn read(&self, path: &Path) -> LocalBoxFuture<'static, Result<Bytes, FetchError>> {
+        utils::fetch_bytes(self.url(path)).boxed_local()
+    }
+}
@@ -0,0 +1,106 @@
+use crate::error::FetchError;
+use crate::path::Path;
+use crate::storage::Storage;
+use bytes::Bytes;
+use futures::future::{self, FutureExt, LocalBoxFuture};
+use seed::browser::web_storage::{LocalStorage, WebStorage};
+use std::collections::BTreeSet;
+
+const DEFAULT_KEY_PREFIX: &str = "semka:";
+
+/// Keeps written files in the browser's `localStorage`
+/// and reads everything else from the inner storage.
+#[derive(Debug, Clone)]
+pub struct LocalStorageOverlay<S> {
+    inner: S,
+    key_prefix: String,
+}
+
+impl<S: Storage> LocalStorageOverlay<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            key_prefix: DEFAULT_KEY_PREFIX.to_string(),
+        }
+    }
+
+    pub fn key_prefix(self, key_prefix: impl AsRef<str>) -> Self {
+        Self {
+            key_prefix: key_prefix.as_ref().to_string(),
+            ..self
+        }
+    }
+
+    /// Drops local changes of the file, so it is read from the inner storage again.
+    pub fn reset(&self, path: &Path) -> Result<(), FetchError> {
+        LocalStorage::remove(self.key(path)).map_err(|err| storage_error(path, err))
+    }
+
+    fn key(&self, path: &Path) -> String {
+        format!("{}{}", self.key_prefix, path)
+    }
+
+    fn local_files(&self) -> Result<Vec<Path>, FetchError> {
+        let root = Path::new();
+        let len = LocalStorage::len().map_err(|err| storage_error(&root, err))?;
+        (0..len)
+            .map(|index| LocalStorage::key(index).map_err(|err| storage_error(&root, err)))
+            .filter_map(|key| match key {
+                Ok(key) => key
+                    .strip_prefix(self.key_prefix.as_str())
+                    .and_then(|path| path.parse().ok())
+                    .map(Ok),
+                Err(err) => Some(Err(err)),
+            })
+            .collect()
+    }
+}
+
+impl<S: Storage> Storage for LocalStorageOverlay<S> {
+    fn read(&self, path: &Path) -> LocalBoxFuture<'static, Result<Bytes, FetchError>> {
+        match LocalStorage::get::<String>(self.key(path)) {
+            Ok(text) => future::ok(Bytes::from(text)).boxed_local(),
+            Err(_) => self.inner.read(path),
+        }
+    }
+
+    fn can_write(&self) -> bool {
+        true
+    }
+    fn write(&self, path: &Path, data: Bytes) -> LocalBoxFuture<'static, Result<(), FetchError>> {
+        let result = String::from_utf8(data.to_vec())
+            .map_err(|err| FetchError::DecodeError(path.to_string(), err.to_string()))
+            .and_then(|text| {
+                LocalStorage::insert(self.key(path), &text).map_err(|err| storage_error(path, err))
+            });
+        future::ready(result).boxed_local()
+    }
+
+    fn can_list(&self) -> bool {
+        true
+    }
+    fn list(&self, path: &Path) -> LocalBoxFuture<'static, Result<Vec<Path>, FetchError>> {
+        let local_files = self.local_files().map(|files| {
+            files
+                .into_iter()
+                .filter(|file| file.is_subpath(path))
+                .filter_map(|file| file.releative_to(path).ok())
+                .collect::<BTreeSet<Path>>()
+        });
+        let inner_files = if self.inner.can_list() {
+            self.inner.list(path)
+        } else {
+            future::ok(vec![]).boxed_local()
+        };
+        async move {
+            let mut files = local_files?;
+            files.extend(inner_files.await?);
+            Ok(files.into_iter().collect())
+        }
+        .boxed_local()
+    }
+}
+
+fn storage_error(path: &Path, err: seed::browser::web_storage::WebStorageError) -> FetchError {
+    FetchError::StorageError(path.to_string(), format!("{:?}", err))
+}
@@ -0,0 +1,68 @@
+use crate::error::FetchError;
+use crate::path::Path;
+use crate::storage::{not_found, Storage};
+use bytes::Bytes;
+use futures::future::{self, FutureExt, LocalBoxFuture};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// Storage that keeps all files in memory. Useful for tests.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    files: Rc<RefCell<BTreeMap<Path, Bytes>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn file(self, path: impl AsRef<str>, data: impl Into<Bytes>) -> Self {
+        let path = path.as_ref().parse().expect("Valid file path");
+        self.insert(path, data.into());
+        self
+    }
+
+    pub fn insert(&self, path: Path, data: Bytes) {
+        self.files.borrow_mut().insert(path, data);
+    }
+
+    pub fn remove(&self, path: &Path) -> Option<Bytes> {
+        self.files.borrow_mut().remove(path)
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn read(&self, path: &Path) -> LocalBoxFuture<'static, Result<Bytes, FetchError>> {
+        let result = self
+            .files
+            .borrow()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| not_found(path));
+        future::ready(result).boxed_local()
+    }
+
+    fn can_write(&self) -> bool {
+        true
+    }
+    fn write(&self, path: &Path, data: Bytes) -> LocalBoxFuture<'static, Result<(), FetchError>> {
+        self.insert(path.clone(), data);
+        future::ok(()).boxed_local()
+    }
+
+    fn can_list(&self) -> bool {
+        true
+    }
+    fn list(&self, path: &Path) -> LocalBoxFuture<'static, Result<Vec<Path>, FetchError>> {
+        let files = self
+            .files
+            .borrow()
+            .keys()
+            .filter(|file| file.is_subpath(path))
+            .filter_map(|file| file.clone().releative_to(path).ok())
+            .collect();
+        future::ok(files).boxed_local()
+    }
+}
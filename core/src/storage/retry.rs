@@ -0,0 +1,199 @@
+use crate::error::FetchError;
+use crate::path::Path;
+use crate::storage::Storage;
+use bytes::Bytes;
+use futures::future::{FutureExt, LocalBoxFuture};
+use seed::prelude::cmds;
+use std::rc::Rc;
+
+#[derive(Derivative, Clone)]
+#[derivative(Debug)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_delay_ms: u32,
+    backoff_factor: u32,
+    max_delay_ms: u32,
+    #[derivative(Debug = "ignore")]
+    is_retryable: fn(&FetchError) -> bool,
+}
+
+impl RetryPolicy {
+    /// Never retries.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+    /// Total number of attempts including the first one.
+    pub fn max_attempts(self, max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            ..self
+        }
+    }
+    pub fn initial_delay_ms(self, initial_delay_ms: u32) -> Self {
+        Self {
+            initial_delay_ms,
+            ..self
+        }
+    }
+    /// Every next delay is multiplied by this factor.
+    pub fn backoff_factor(self, backoff_factor: u32) -> Self {
+        Self {
+            backoff_factor,
+            ..self
+        }
+    }
+    pub fn max_delay_ms(self, max_delay_ms: u32) -> Self {
+        Self {
+            max_delay_ms,
+            ..self
+        }
+    }
+    /// Decides which errors are worth another attempt.
+    /// By default only `FetchError::is_transient` errors are retried.
+    pub fn retry_if(self, is_retryable: fn(&FetchError) -> bool) -> Self {
+        Self {
+            is_retryable,
+            ..self
+        }
+    }
+
+    /// Delay before the attempt following `attempt` (counting from 1).
+    fn delay_ms(&self, attempt: u32) -> u32 {
+        (1..attempt)
+            .fold(self.initial_delay_ms, |delay, _| {
+                delay.saturating_mul(self.backoff_factor)
+            })
+            .min(self.max_delay_ms)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay_ms: 500,
+            backoff_factor: 2,
+            max_delay_ms: 8000,
+            is_retryable: FetchError::is_transient,
+        }
+    }
+}
+
+/// Retries failed reads of the inner storage according to the policy.
+#[derive(Debug)]
+pub struct RetryStorage {
+    inner: Rc<dyn Storage>,
+    policy: RetryPolicy,
+}
+
+impl RetryStorage {
+    pub fn new(inner: Rc<dyn Storage>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+}
+
+impl Storage for RetryStorage {
+    fn read(&self, path: &Path) -> LocalBoxFuture<'static, Result<Bytes, FetchError>> {
+        let inner = self.inner.clone();
+        let policy = self.policy.clone();
+        let path = path.clone();
+        async move {
+            let mut attempt = 1;
+            loop {
+                match inner.read(&path).await {
+                    Err(err) if attempt < policy.max_attempts && (policy.is_retryable)(&err) => {
+                        cmds::timeout(policy.delay_ms(attempt), || ()).await;
+                        attempt += 1;
+                    }
+                    result => return result,
+                }
+            }
+        }
+        .boxed_local()
+    }
+
+    fn can_write(&self) -> bool {
+        self.inner.can_write()
+    }
+    fn write(&self, path: &Path, data: Bytes) -> LocalBoxFuture<'static, Result<(), FetchError>> {
+        self.inner.write(path, data)
+    }
+
+    fn can_list(&self) -> bool {
+        self.inner.can_list()
+    }
+    fn list(&self, path: &Path) -> LocalBoxFuture<'static, Result<Vec<Path>, FetchError>> {
+        self.inner.list(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::future;
+    use std::cell::Cell;
+
+    // Fails every read with the error and counts reads.
+    #[derive(Debug)]
+    struct Failing {
+        error: FetchError,
+        reads: Cell<u32>,
+    }
+
+    impl Storage for Failing {
+        fn read(&self, _path: &Path) -> LocalBoxFuture<'static, Result<Bytes, FetchError>> {
+            self.reads.set(self.reads.get() + 1);
+            future::err(self.error.clone()).boxed_local()
+        }
+    }
+
+    fn read_count(error: FetchError, policy: RetryPolicy) -> u32 {
+        let inner = Rc::new(Failing {
+            error,
+            reads: Cell::new(0),
+        });
+        let storage = RetryStorage::new(inner.clone(), policy);
+        assert!(block_on(storage.read(&Path::new())).is_err());
+        inner.reads.get()
+    }
+
+    fn network_error() -> FetchError {
+        FetchError::NetworkError("a".to_string(), "offline".to_string())
+    }
+
+    #[test]
+    fn exponential_backoff() {
+        let policy = RetryPolicy::default();
+        let delays: Vec<u32> = (1..=6).map(|attempt| policy.delay_ms(attempt)).collect();
+        assert_eq!(delays, vec![500, 1000, 2000, 4000, 8000, 8000]);
+        let policy = policy
+            .initial_delay_ms(100)
+            .backoff_factor(3)
+            .max_delay_ms(1000);
+        let delays: Vec<u32> = (1..=4).map(|attempt| policy.delay_ms(attempt)).collect();
+        assert_eq!(delays, vec![100, 300, 900, 1000]);
+        assert_eq!(policy.max_delay_ms(u32::MAX).delay_ms(100), u32::MAX);
+    }
+
+    #[test]
+    fn at_least_one_attempt() {
+        assert_eq!(RetryPolicy::default().max_attempts(0).max_attempts, 1);
+        assert_eq!(read_count(network_error(), RetryPolicy::none()), 1);
+    }
+
+    #[test]
+    fn no_retry_of_permanent_errors() {
+        let not_found = FetchError::ClientError {
+            url: "a".to_string(),
+            code: 404,
+            text: "Not Found".to_string(),
+        };
+        assert_eq!(read_count(not_found, RetryPolicy::default()), 1);
+        let policy = RetryPolicy::default().retry_if(|_| false);
+        assert_eq!(read_count(network_error(), policy), 1);
+    }
+}
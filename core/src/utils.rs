@@ -2,10 +2,12 @@ use crate::constants;
 use crate::error;
 use crate::manifests;
 use crate::path;
+use crate::storage::{self, Storage};
 use bytes::Bytes;
-use futures::future::{Future, TryFutureExt};
+use futures::future::{self, Future, FutureExt, TryFutureExt};
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::rc::Rc;
 
 pub fn fetch_bytes<U>(url: U) -> impl Future<Output = Result<Bytes, error::FetchError>>
 where
@@ -60,26 +62,108 @@ where
     .map_err(|err| error::FetchError::from_seed(url_clone, err))
 }
 
-pub async fn fetch_site_manifest() -> Result<manifests::SiteManifest, error::FetchError> {
-    fetch_json(constants::SITE_MANIFEST_FILE).await
+pub fn fetch_site_manifest(
+    storage: &dyn Storage,
+) -> impl Future<Output = Result<manifests::SiteManifest, error::FetchError>> + 'static {
+    let site_manifest_path = path::Path::new().add(constants::SITE_MANIFEST_FILE);
+    storage::read_json(storage, &site_manifest_path)
 }
 
-pub async fn fetch_doc_manifest(
-    doc_name: impl AsRef<str>,
-) -> Result<manifests::DocManifest, error::FetchError> {
-    let doc_name = doc_name.as_ref();
-    if doc_name.is_empty() {
-        return Err(error::FetchError::RequestError(
-            doc_name.to_string(),
-            "Document name is empty".to_string(),
-        ));
+pub fn fetch_doc_manifest(
+    storage: &dyn Storage,
+    doc_path: &path::Path,
+) -> impl Future<Output = Result<manifests::DocManifest, error::FetchError>> + 'static {
+    let doc_dir = if doc_path.is_empty() {
+        Err("Document name is empty".to_string())
+    } else {
+        path::Path::new()
+            .add(constants::DOC_DIR)
+            .join_confined(doc_path)
+            .map_err(|err| err.to_string())
+    };
+    match doc_dir {
+        Ok(doc_dir) => {
+            storage::read_json(storage, &doc_dir.add(constants::DOC_MANIFEST_FILE)).right_future()
+        }
+        Err(err) => {
+            future::err(error::FetchError::RequestError(doc_path.to_string(), err)).left_future()
+        }
     }
-    let doc_manifest_path = path::Path::new()
+}
+
+/// Finds the document `page_path` belongs to.
+/// Paths under master pages of `site_manifest` belong to them. Other paths
+/// are probed from longer prefixes down to the longest page of the site
+/// manifest they are under, that page is taken without probing further.
+/// Returns the document with its manifest,
+/// the rest of `page_path` is left to the widget of the document.
+pub fn find_doc_manifest(
+    storage: Rc<dyn Storage>,
+    page_path: path::Path,
+    site_manifest: &manifests::SiteManifest,
+) -> impl Future<Output = Result<(path::Path, manifests::DocManifest), error::FetchError>> + 'static
+{
+    let is_under = |page: &path::Path| *page == page_path || page_path.is_subpath(page);
+    let master_page = site_manifest
+        .master_pages()
+        .into_iter()
+        .filter(|master_page| is_under(master_page))
+        .max_by_key(|master_page| master_page.len())
+        .cloned();
+    // Shortest prefix worth probing.
+    let known_len = site_manifest
+        .pages()
+        .into_iter()
+        .filter(|page| !page.is_empty() && is_under(page))
+        .map(path::Path::len)
+        .max()
+        .unwrap_or(1);
+    async move {
+        let mut document = match master_page {
+            Some(master_page) => {
+                return fetch_doc_manifest(&*storage, &master_page)
+                    .await
+                    .map(|manifest| (master_page, manifest))
+            }
+            None => page_path,
+        };
+        loop {
+            match fetch_doc_manifest(&*storage, &document).await {
+                Err(err) if err.is_not_found() && document.len() > known_len => {
+                    document = document.iter().take(document.len() - 1).collect();
+                }
+                result => return result.map(|manifest| (document, manifest)),
+            }
+        }
+    }
+}
+
+/// Splits `page_path` into the longest prefix `is_document` accepts
+/// and the rest of the path for the widget of the document.
+pub fn split_doc_path(
+    page_path: &path::Path,
+    is_document: impl Fn(&path::Path) -> bool,
+) -> Option<(path::Path, path::Path)> {
+    (1..=page_path.len())
+        .rev()
+        .map(|len| page_path.iter().take(len).collect::<path::Path>())
+        .find(|document| is_document(document))
+        .map(|document| {
+            let sub_path = page_path.iter().skip(document.len()).collect();
+            (document, sub_path)
+        })
+}
+
+/// Path of the file the widget of `document` requests,
+/// the file has to be in the document directory.
+pub fn doc_file_path(
+    document: &path::Path,
+    file_path: &path::Path,
+) -> Result<path::Path, error::PathError> {
+    path::Path::new()
         .add(constants::DOC_DIR)
-        .add(doc_name)
-        .add(constants::DOC_MANIFEST_FILE);
-    let manifest: manifests::DocManifest = fetch_json(doc_manifest_path).await?;
-    Ok(manifest)
+        .join(document)
+        .join_confined(file_path)
 }
 
 pub fn show_spinner<Ms>() -> seed::prelude::Node<Ms> {
@@ -97,3 +181,56 @@ pub fn is_url_absolute(url: impl AsRef<str>) -> bool {
 pub fn fmt_as_type_name<T>(_: &T, f: &mut std::fmt::Formatter) -> std::fmt::Result {
     write!(f, "{}", std::any::type_name::<T>())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use futures::executor::block_on;
+
+    fn path(path: &str) -> path::Path {
+        path.parse().unwrap()
+    }
+
+    fn find(page_path: &str, site_manifest: &str) -> Result<path::Path, error::FetchError> {
+        let storage = MemoryStorage::new()
+            .file("_doc/blog/doc_manifest.json", r#"{"widget": "blog"}"#)
+            .file("_doc/blog/2020/doc_manifest.json", r#"{"widget": "year"}"#)
+            .file("_doc/master/doc_manifest.json", r#"{"widget": "master"}"#);
+        let site_manifest: manifests::SiteManifest = serde_json::from_str(site_manifest).unwrap();
+        block_on(find_doc_manifest(
+            Rc::new(storage),
+            path(page_path),
+            &site_manifest.normalized(),
+        ))
+        .map(|(document, _)| document)
+    }
+
+    #[test]
+    fn find_longest_prefix() {
+        assert_eq!(find("blog", "{}").unwrap(), path("blog"));
+        assert_eq!(find("blog/2020", "{}").unwrap(), path("blog/2020"));
+        assert_eq!(find("blog/2020/post", "{}").unwrap(), path("blog/2020"));
+        assert_eq!(find("blog/2021/post", "{}").unwrap(), path("blog"));
+        assert!(find("missing/post", "{}").unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn find_under_known_pages() {
+        let site_manifest = r#"{"indexPage": "blog", "masterPage": "master"}"#;
+        // Documents below a known page still take precedence.
+        assert_eq!(
+            find("blog/2020/post", site_manifest).unwrap(),
+            path("blog/2020")
+        );
+        assert_eq!(find("blog/post", site_manifest).unwrap(), path("blog"));
+        assert_eq!(find("master/blog", site_manifest).unwrap(), path("master"));
+    }
+
+    #[test]
+    fn split_sub_path() {
+        let split = split_doc_path(&path("blog/2020/post"), |doc| *doc == path("blog"));
+        assert_eq!(split, Some((path("blog"), path("2020/post"))));
+        assert_eq!(split_doc_path(&path("blog"), |_| false), None);
+    }
+}
@@ -1,15 +1,23 @@
+use crate::check::Reference;
 use crate::context::Context;
 use crate::error::{FetchError, WidgetError};
 use crate::manifests::DocManifest;
 use crate::path::Path;
+use crate::storage::Storage;
 use bytes::Bytes;
 use failure::Error;
+use futures::future::{self, FutureExt, LocalBoxFuture};
 use std::any::Any;
+use std::rc::Rc;
 
 mod dependencies;
+pub(crate) mod driver;
+mod name;
 mod orders;
 
 pub use dependencies::Dependencies;
+pub(crate) use dependencies::{find_cycles, Cycles};
+pub use name::{Version, WidgetName};
 pub use orders::{WidgetCmd, WidgetOrders};
 
 pub trait Widget: std::fmt::Debug {
@@ -34,13 +42,40 @@ pub trait Widget: std::fmt::Debug {
 #[derive(Debug)]
 pub enum WidgetMsg {
     CmdResult(Box<dyn Any>),
+    /// Widget's own messages, e.g. from event handlers in its view.
+    Custom(Box<dyn Any>),
+    /// Routes message to the widget of the given document.
+    /// Widgets never receive it.
+    ForDocument(Path, Box<WidgetMsg>),
     FetchBytesResult(Path, Result<Bytes, FetchError>),
     FetchJsonResult(Path, Result<serde_json::Value, FetchError>),
     FetchTextResult(Path, Result<String, FetchError>),
+    /// Query parameters of the page URL changed, see `Context::query`.
+    /// Sent to widgets of the current page only.
+    QueryChanged,
 }
 
 pub trait WidgetFactory: std::fmt::Debug {
     fn can_handle(&self) -> &'static [&'static str];
-    fn create(&self, doc_path: Path, manifest: DocManifest)
-        -> Result<Box<dyn Widget>, WidgetError>;
+    /// Creates the widget of `document` for the page `document/sub_path`.
+    /// Widgets with own routes handle `sub_path`, e.g. a blog
+    /// at `blog` gets `2020/post` for the page `blog/2020/post`.
+    fn create(
+        &self,
+        document: Path,
+        sub_path: Path,
+        manifest: DocManifest,
+    ) -> Result<Box<dyn Widget>, WidgetError>;
+
+    /// Documents the widget of the page `document/sub_path` depends on,
+    /// found without running the widget. Used by offline site checks.
+    fn static_dependencies(
+        &self,
+        _document: &Path,
+        _sub_path: &Path,
+        _manifest: &DocManifest,
+        _storage: Rc<dyn Storage>,
+    ) -> LocalBoxFuture<'static, Result<Vec<Reference>, WidgetError>> {
+        future::ok(vec![]).boxed_local()
+    }
 }
@@ -1,17 +1,26 @@
 use crate::builtin_widgets;
-use crate::constants::MAX_WIDGET_RECURSION;
+use crate::constants::{DOC_PATH_ATTR, MAX_WIDGET_RECURSION};
 use crate::context::Context;
+use crate::manifests::PageMeta;
+use crate::node_ext::resolve_urls;
 use crate::path::Path;
 use crate::widget::{Widget, WidgetMsg};
 use failure::format_err;
+use itertools::Itertools;
 use seed::prelude::*;
 use std::collections::{BTreeMap, BTreeSet};
 
+/// Dependencies `(from, to)` that close cycles, with the cycles themselves.
+pub(crate) type Cycles = BTreeMap<(Path, Path), Vec<Path>>;
+
 #[derive(Clone, Copy)]
 pub struct Dependencies<'a> {
     doc_path: Option<&'a Path>,
     widgets: &'a BTreeMap<Path, Box<dyn Widget>>,
+    documents: &'a BTreeMap<Path, Path>,
     dependencies: &'a BTreeMap<Path, BTreeSet<Path>>,
+    cycles: &'a Cycles,
+    meta: &'a BTreeMap<Path, PageMeta>,
     recursion_level: usize,
     ctx: &'a Context,
 }
@@ -19,13 +28,19 @@ pub struct Dependencies<'a> {
 impl<'a> Dependencies<'a> {
     pub(crate) fn new(
         widgets: &'a BTreeMap<Path, Box<dyn Widget>>,
+        documents: &'a BTreeMap<Path, Path>,
         dependencies: &'a BTreeMap<Path, BTreeSet<Path>>,
+        cycles: &'a Cycles,
+        meta: &'a BTreeMap<Path, PageMeta>,
         ctx: &'a Context,
     ) -> Self {
         Self {
             doc_path: None,
             widgets,
+            documents,
             dependencies,
+            cycles,
+            meta,
             recursion_level: 0,
             ctx,
         }
@@ -36,13 +51,29 @@ impl<'a> Dependencies<'a> {
             doc_path: Some(doc_path),
             recursion_level: self.recursion_level + 1,
             widgets: self.widgets,
+            documents: self.documents,
             dependencies: self.dependencies,
+            cycles: self.cycles,
+            meta: self.meta,
             ctx: self.ctx,
         }
     }
 
+    /// Metadata of the loaded document at `path`, e.g. for lists of pages.
+    pub fn meta(&self, path: &Path) -> Option<&'a PageMeta> {
+        self.meta.get(path)
+    }
+
     pub fn view(&self, path: &'a Path) -> Node<WidgetMsg> {
-        let mut node = if self.recursion_level > MAX_WIDGET_RECURSION {
+        let cycle = self
+            .doc_path
+            .and_then(|self_path| self.cycles.get(&(self_path.clone(), path.clone())));
+        let node = if let Some(cycle) = cycle {
+            Err(format_err!(
+                "Dependency cycle: {}",
+                cycle.iter().join(" -> ")
+            ))
+        } else if self.recursion_level > MAX_WIDGET_RECURSION {
             Err(format_err!("Recursion level exceeded"))
         } else if self
             .doc_path
@@ -78,7 +109,93 @@ impl<'a> Dependencies<'a> {
             )
             .view(self.dig_in(path), self.ctx)
         });
-        node.add_attr("data-doc-path", path.to_string());
-        node
+        // Links of the widget are relative to its document.
+        let mut node = resolve_urls(node, self.documents.get(path).unwrap_or(path), self.ctx);
+        node.add_attr(DOC_PATH_ATTR, path.to_string());
+        let path = path.clone();
+        node.map_msg(move |msg| match msg {
+            WidgetMsg::ForDocument(..) => msg,
+            msg => WidgetMsg::ForDocument(path, Box::new(msg)),
+        })
+    }
+}
+
+/// Finds dependencies that close cycles. Search starts from `root`,
+/// so the cycle is broken where the page rendering would enter it again.
+pub(crate) fn find_cycles(dependencies: &BTreeMap<Path, BTreeSet<Path>>, root: &Path) -> Cycles {
+    fn visit(
+        path: &Path,
+        dependencies: &BTreeMap<Path, BTreeSet<Path>>,
+        stack: &mut Vec<Path>,
+        done: &mut BTreeSet<Path>,
+        cycles: &mut Cycles,
+    ) {
+        stack.push(path.clone());
+        for dep in dependencies.get(path).into_iter().flatten() {
+            if let Some(start) = stack.iter().position(|p| p == dep) {
+                let cycle = stack[start..].iter().chain(Some(dep)).cloned().collect();
+                cycles.insert((path.clone(), dep.clone()), cycle);
+            } else if !done.contains(dep) {
+                visit(dep, dependencies, stack, done, cycles);
+            }
+        }
+        stack.pop();
+        done.insert(path.clone());
+    }
+
+    let mut cycles = Cycles::new();
+    let mut done = BTreeSet::new();
+    for path in Some(root).into_iter().chain(dependencies.keys()) {
+        if !done.contains(path) {
+            visit(path, dependencies, &mut vec![], &mut done, &mut cycles);
+        }
+    }
+    cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(path: &str) -> Path {
+        path.parse().unwrap()
+    }
+
+    fn dependencies(edges: &[(&str, &[&str])]) -> BTreeMap<Path, BTreeSet<Path>> {
+        edges
+            .iter()
+            .map(|(from, to)| (path(from), to.iter().map(|to| path(to)).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn no_cycles() {
+        let deps = dependencies(&[("a", &["b", "c"]), ("b", &["c"])]);
+        assert!(find_cycles(&deps, &path("a")).is_empty());
+    }
+
+    #[test]
+    fn cycle_broken_where_entered_again() {
+        let deps = dependencies(&[("a", &["b"]), ("b", &["c"]), ("c", &["b"])]);
+        let cycles = find_cycles(&deps, &path("a"));
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(
+            cycles.get(&(path("c"), path("b"))),
+            Some(&vec![path("b"), path("c"), path("b")])
+        );
+        // From `c` the same cycle is entered through the other dependency.
+        let cycles = find_cycles(&deps, &path("c"));
+        assert!(cycles.contains_key(&(path("b"), path("c"))));
+    }
+
+    #[test]
+    fn self_dependency_and_unreachable_cycles() {
+        let deps = dependencies(&[("a", &["a"]), ("x", &["y"]), ("y", &["x"])]);
+        let cycles = find_cycles(&deps, &path("a"));
+        assert_eq!(
+            cycles.get(&(path("a"), path("a"))),
+            Some(&vec![path("a"), path("a")])
+        );
+        assert_eq!(cycles.len(), 2);
     }
 }
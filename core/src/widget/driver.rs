@@ -0,0 +1,116 @@
+//! Widget handling shared by browse mode and pre-rendering.
+//!
+//! Drivers differ only in how they run futures and deliver messages,
+//! what widgets get and what their commands mean is decided here.
+
+use crate::builtin_widgets;
+use crate::context::Context;
+use crate::error::FetchError;
+use crate::head::HeadElement;
+use crate::manifests::{DocManifest, PageMeta};
+use crate::notification::Notification;
+use crate::path::Path;
+use crate::storage;
+use crate::utils;
+use crate::widget::{Widget, WidgetCmd, WidgetMsg, WidgetOrders};
+use failure::Error;
+use futures::future::{FutureExt, LocalBoxFuture};
+use std::collections::BTreeSet;
+
+/// Widget command with its files resolved, to be carried out by the driver.
+pub(crate) enum Order {
+    /// Future of the message for the widget.
+    Perform(LocalBoxFuture<'static, WidgetMsg>),
+    Notify(Notification),
+    Reload,
+    UpdateDependencies(BTreeSet<Path>),
+    UpdateHead(BTreeSet<HeadElement>),
+    UpdateMeta(PageMeta),
+    Skip,
+    /// The widget failed, it gets no further orders.
+    Fail(Error),
+}
+
+/// Widget for `doc_path` from the result of `utils::find_doc_manifest`.
+/// Missing documents are shown with the not found page.
+pub(crate) fn create_widget(
+    doc_path: &Path,
+    found: Result<(Path, DocManifest), FetchError>,
+    ctx: &Context,
+) -> Result<Box<dyn Widget>, Error> {
+    match found {
+        Err(err) if err.is_not_found() && *doc_path != ctx.site_manifest.not_found_page => {
+            Ok(builtin_widgets::NotFound::new(
+                doc_path.clone(),
+                ctx.site_manifest.not_found_page.clone(),
+            ))
+        }
+        Err(err) => Err(err.into()),
+        Ok((document, manifest)) => {
+            let sub_path = doc_path.iter().skip(document.len()).collect();
+            Ok(ctx
+                .registry
+                .get_widget(&manifest.widget)?
+                .create(document, sub_path, manifest)?)
+        }
+    }
+}
+
+/// Orders for commands of the widget of `document`.
+/// Files are read from the document directory. A command that can not be
+/// carried out ends the orders with `Order::Fail`, the rest is dropped.
+pub(crate) fn interpret_orders(
+    w_orders: WidgetOrders,
+    document: &Path,
+    ctx: &Context,
+) -> Vec<Order> {
+    let mut orders = Vec::new();
+    for cmd in w_orders.orders {
+        let order = match interpret_cmd(cmd, document, ctx) {
+            Ok(order) => order,
+            Err(err) => {
+                orders.push(Order::Fail(err));
+                break;
+            }
+        };
+        orders.push(order);
+    }
+    orders
+}
+
+fn interpret_cmd(cmd: WidgetCmd, document: &Path, ctx: &Context) -> Result<Order, Error> {
+    Ok(match cmd {
+        WidgetCmd::FetchBytes(path) => {
+            let file_path = utils::doc_file_path(document, &path)?;
+            Order::Perform(
+                ctx.storage
+                    .read(&file_path)
+                    .map(|result| WidgetMsg::FetchBytesResult(path, result))
+                    .boxed_local(),
+            )
+        }
+        WidgetCmd::FetchJson(path) => {
+            let file_path = utils::doc_file_path(document, &path)?;
+            Order::Perform(
+                storage::read_json::<serde_json::Value>(&*ctx.storage, &file_path)
+                    .map(|result| WidgetMsg::FetchJsonResult(path, result))
+                    .boxed_local(),
+            )
+        }
+        WidgetCmd::FetchText(path) => {
+            let file_path = utils::doc_file_path(document, &path)?;
+            Order::Perform(
+                storage::read_text(&*ctx.storage, &file_path)
+                    .map(|result| WidgetMsg::FetchTextResult(path, result))
+                    .boxed_local(),
+            )
+        }
+        WidgetCmd::PerformCmd(fut) => Order::Perform(fut.map(WidgetMsg::CmdResult).boxed_local()),
+        WidgetCmd::Notify(notification) => Order::Notify(notification),
+        WidgetCmd::Reload => Order::Reload,
+        WidgetCmd::UpdateDependencies(dependencies) => Order::UpdateDependencies(dependencies),
+        WidgetCmd::UpdateHead(elements) => Order::UpdateHead(elements),
+        WidgetCmd::UpdateMeta(meta) => Order::UpdateMeta(meta),
+        WidgetCmd::Skip => Order::Skip,
+    })
+}
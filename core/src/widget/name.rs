@@ -0,0 +1,91 @@
+/// Widget name in form of `<vendor>-<version>-<name>`, e.g. `semka-0.1-markdown`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct WidgetName {
+    pub vendor: String,
+    pub version: Version,
+    pub name: String,
+}
+
+/// Version with up to three numeric parts. Missing parts are zeros.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl WidgetName {
+    pub fn parse(widget: &str) -> Option<Self> {
+        let mut parts = widget.splitn(3, '-');
+        let vendor = parts.next().filter(|s| !s.is_empty())?;
+        let version = Version::parse(parts.next()?)?;
+        let name = parts.next().filter(|s| !s.is_empty())?;
+        Some(Self {
+            vendor: vendor.to_string(),
+            version,
+            name: name.to_string(),
+        })
+    }
+
+    /// Same widget of the same vendor regardless of version.
+    pub fn is_same_widget(&self, other: &Self) -> bool {
+        self.vendor == other.vendor && self.name == other.name
+    }
+
+    /// Whether this implementation can serve documents that request `requested`.
+    pub fn is_compatible_with(&self, requested: &Self) -> bool {
+        self.is_same_widget(requested) && self.version.is_compatible_with(&requested.version)
+    }
+}
+
+impl std::fmt::Display for WidgetName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}-{}", self.vendor, self.version, self.name)
+    }
+}
+
+impl Version {
+    pub fn parse(version: &str) -> Option<Self> {
+        let parts = version
+            .split('.')
+            .map(|part| part.parse::<u64>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        match parts.as_slice() {
+            [major] => Some(Self::new(*major, 0, 0)),
+            [major, minor] => Some(Self::new(*major, *minor, 0)),
+            [major, minor, patch] => Some(Self::new(*major, *minor, *patch)),
+            _ => None,
+        }
+    }
+
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Follows Cargo rules: the first non-zero part must match
+    /// and this version must not be older than `requested`.
+    pub fn is_compatible_with(&self, requested: &Self) -> bool {
+        let same_series = if requested.major != 0 {
+            self.major == requested.major
+        } else if requested.minor != 0 {
+            self.major == 0 && self.minor == requested.minor
+        } else {
+            self.major == 0 && self.minor == 0 && self.patch == requested.patch
+        };
+        same_series && self >= requested
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)?;
+        if self.patch != 0 {
+            write!(f, ".{}", self.patch)?;
+        }
+        Ok(())
+    }
+}
@@ -1,3 +1,6 @@
+use crate::head::HeadElement;
+use crate::manifests::PageMeta;
+use crate::notification::Notification;
 use crate::path::Path;
 use futures::future::{BoxFuture, Future, FutureExt};
 use std::any::Any;
@@ -35,10 +38,35 @@ impl WidgetOrders {
         )));
         self
     }
+    pub fn notify(mut self, notification: Notification) -> Self {
+        self.orders.push_back(WidgetCmd::Notify(notification));
+        self
+    }
+    pub fn warn(self, message: impl ToString) -> Self {
+        self.notify(Notification::warning(message))
+    }
     pub fn update_deps(mut self, deps: BTreeSet<Path>) -> Self {
         self.orders.push_front(WidgetCmd::UpdateDependencies(deps));
         self
     }
+    /// Replaces elements this document puts into the document head.
+    /// They stay while the document is shown.
+    pub fn update_head(mut self, elements: BTreeSet<HeadElement>) -> Self {
+        self.orders.push_back(WidgetCmd::UpdateHead(elements));
+        self
+    }
+    /// Replaces metadata of this document, initially taken from its manifest.
+    /// Page metadata is shown in the document head,
+    /// widgets read metadata of their dependencies with `Dependencies::meta`.
+    pub fn update_meta(mut self, meta: PageMeta) -> Self {
+        self.orders.push_back(WidgetCmd::UpdateMeta(meta));
+        self
+    }
+    /// Loads the document of this widget again from scratch.
+    pub fn reload(mut self) -> Self {
+        self.orders.push_back(WidgetCmd::Reload);
+        self
+    }
     pub fn skip(mut self) -> Self {
         self.orders.push_front(WidgetCmd::Skip);
         self
@@ -55,6 +83,10 @@ pub enum WidgetCmd {
         #[derivative(Debug(format_with = "crate::utils::fmt_as_type_name"))]
         BoxFuture<'static, Box<dyn Any>>,
     ),
+    Notify(Notification),
+    Reload,
     UpdateDependencies(BTreeSet<Path>),
+    UpdateHead(BTreeSet<HeadElement>),
+    UpdateMeta(PageMeta),
     Skip,
 }
@@ -0,0 +1,368 @@
+//! Minimal CSS rewriting without full parsing.
+
+use semka_core::path::Path;
+use semka_core::utils::is_url_absolute;
+
+/// At-rules with nested style rules that have to be scoped too.
+const NESTED_AT_RULES: &[&str] = &["media", "supports", "document", "layer", "container"];
+
+/// Selectors that mean the whole page and are replaced with the scope.
+const ROOT_SELECTORS: &[&str] = &[":root", "html", "body"];
+
+/// Prefixes every selector of `css` with `scope`,
+/// so rules only apply to descendants of the scope element.
+pub fn scope_css(css: &str, scope: &str) -> String {
+    let mut scoped = String::with_capacity(css.len());
+    scope_rules(&strip_comments(css), scope, &mut scoped);
+    scoped
+}
+
+/// Selector of elements with attribute `name` equal to `value`.
+/// The value is escaped as a CSS string.
+pub fn attribute_selector(name: &str, value: &str) -> String {
+    let mut selector = format!("[{}=\"", name);
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                selector.push('\\');
+                selector.push(c);
+            }
+            // Hex escapes end with a space, so following hex digits are not taken into them.
+            c if c.is_control() => selector.push_str(&format!("\\{:x} ", c as u32)),
+            c => selector.push(c),
+        }
+    }
+    selector.push_str("\"]");
+    selector
+}
+
+/// Makes relative `url()` and `@import` targets of `css` relative to `dir`,
+/// so rules of a file in `dir` keep working when put into the page.
+pub fn rebase_urls(css: &str, dir: &Path) -> String {
+    let mut rebased = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some((start, end)) = find_url(rest) {
+        rebased.push_str(&rest[..start]);
+        rebased.push_str(&rebase_url(&rest[start..end], dir));
+        rest = &rest[end..];
+        // Closing quote is copied as is, so it does not open a string.
+        if rest.starts_with('"') || rest.starts_with('\'') {
+            rebased.push_str(&rest[..1]);
+            rest = &rest[1..];
+        }
+    }
+    rebased.push_str(rest);
+    rebased
+}
+
+fn rebase_url(url: &str, dir: &Path) -> String {
+    if url.is_empty() || url.starts_with('#') || is_url_absolute(url) {
+        return url.to_string();
+    }
+    let (path, suffix) = url.split_at(url.find(&['?', '#'][..]).unwrap_or(url.len()));
+    match path.parse::<Path>() {
+        Ok(path) => format!("{}{}", dir.join(&path).normalize(), suffix),
+        Err(_) => url.to_string(),
+    }
+}
+
+/// Range of the first `url()` argument or `@import` string outside of strings.
+fn find_url(css: &str) -> Option<(usize, usize)> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (pos, c) in css.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (_, '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, _) => {
+                let rest = &css[pos..];
+                let word_start = !css[..pos]
+                    .ends_with(|c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+                if word_start && starts_with_ignore_case(rest, "url(") {
+                    return Some(argument_range(css, pos + "url(".len(), true));
+                }
+                if starts_with_ignore_case(rest, "@import") {
+                    let start = pos + "@import".len();
+                    let arg = css[start..].trim_start();
+                    if arg.starts_with('"') || arg.starts_with('\'') {
+                        return Some(argument_range(css, css.len() - arg.len(), false));
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Range of the string at `start` without quotes
+/// or, if `unquoted` is allowed, of the bare word there.
+fn argument_range(css: &str, start: usize, unquoted: bool) -> (usize, usize) {
+    let start = css.len() - css[start..].trim_start().len();
+    match css[start..].chars().next() {
+        Some(q) if q == '"' || q == '\'' => {
+            let end = css[start + 1..]
+                .find(q)
+                .map_or(css.len(), |pos| start + 1 + pos);
+            (start + 1, end)
+        }
+        _ if unquoted => {
+            let end = css[start..]
+                .find(|c: char| c == ')' || c.is_whitespace())
+                .map_or(css.len(), |pos| start + pos);
+            (start, end)
+        }
+        _ => (start, start),
+    }
+}
+
+fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    text.len() >= prefix.len()
+        && text.is_char_boundary(prefix.len())
+        && text[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+fn scope_rules(css: &str, scope: &str, out: &mut String) {
+    let mut rest = css.trim_start();
+    while !rest.is_empty() {
+        match find_outside(rest, |c| c == '{' || c == ';') {
+            Some(pos) if rest[pos..].starts_with(';') => {
+                out.push_str(&rest[..=pos]);
+                out.push('\n');
+                rest = &rest[pos + 1..];
+            }
+            Some(pos) => {
+                let prelude = rest[..pos].trim();
+                let end = matching_brace(rest, pos).unwrap_or(rest.len());
+                let block = &rest[pos + 1..end];
+                if let Some(at_rule) = prelude.strip_prefix('@') {
+                    let name: String = at_rule
+                        .chars()
+                        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+                        .collect();
+                    out.push_str(prelude);
+                    out.push_str(" {\n");
+                    if NESTED_AT_RULES.contains(&name.to_ascii_lowercase().as_str()) {
+                        scope_rules(block, scope, out);
+                    } else {
+                        out.push_str(block.trim());
+                        out.push('\n');
+                    }
+                    out.push_str("}\n");
+                } else {
+                    let selectors: Vec<String> = split_selectors(prelude)
+                        .into_iter()
+                        .map(|selector| scope_selector(selector, scope))
+                        .collect();
+                    out.push_str(&selectors.join(", "));
+                    out.push_str(" {");
+                    out.push_str(block);
+                    out.push_str("}\n");
+                }
+                rest = rest.get(end + 1..).unwrap_or("");
+            }
+            None => {
+                out.push_str(rest);
+                break;
+            }
+        }
+        rest = rest.trim_start();
+    }
+}
+
+fn scope_selector(selector: &str, scope: &str) -> String {
+    let mut selector = selector.trim();
+    loop {
+        let root = ROOT_SELECTORS.iter().find(|root| {
+            selector.len() >= root.len()
+                && selector[..root.len()].eq_ignore_ascii_case(root)
+                && !selector[root.len()..]
+                    .starts_with(|c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        });
+        match root {
+            Some(root) => {
+                let rest = &selector[root.len()..];
+                if rest.starts_with(char::is_whitespace) {
+                    selector = rest.trim_start();
+                } else {
+                    // `body` or `body.dark` mean the scope element itself.
+                    return format!("{}{}", scope, rest);
+                }
+            }
+            None => return format!("{} {}", scope, selector),
+        }
+    }
+}
+
+/// Splits comma separated selectors, commas in brackets and strings are kept.
+fn split_selectors(prelude: &str) -> Vec<&str> {
+    let mut selectors = vec![];
+    let mut rest = prelude;
+    while let Some(pos) = find_outside(rest, |c| c == ',') {
+        selectors.push(&rest[..pos]);
+        rest = &rest[pos + 1..];
+    }
+    selectors.push(rest);
+    selectors
+        .into_iter()
+        .map(str::trim)
+        .filter(|selector| !selector.is_empty())
+        .collect()
+}
+
+/// Position of the first char matching `pred` outside of strings and brackets.
+fn find_outside(text: &str, pred: impl Fn(char) -> bool) -> Option<usize> {
+    let mut quote = None;
+    let mut depth = 0usize;
+    let mut escaped = false;
+    for (pos, c) in text.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (_, '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '(') | (None, '[') => depth += 1,
+            (None, ')') | (None, ']') => depth = depth.saturating_sub(1),
+            (None, c) if depth == 0 && pred(c) => return Some(pos),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Position of the brace closing one at `open`.
+fn matching_brace(text: &str, open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut rest = &text[open..];
+    let mut offset = open;
+    while let Some(pos) = find_outside(rest, |c| c == '{' || c == '}') {
+        if rest[pos..].starts_with('{') {
+            depth += 1;
+        } else {
+            depth -= 1;
+            if depth == 0 {
+                return Some(offset + pos);
+            }
+        }
+        offset += pos + 1;
+        rest = &rest[pos + 1..];
+    }
+    None
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut stripped = String::with_capacity(css.len());
+    let mut rest = css;
+    loop {
+        match find_outside(rest, |c| c == '/') {
+            Some(pos) if rest[pos..].starts_with("/*") => {
+                stripped.push_str(&rest[..pos]);
+                rest = match rest[pos + 2..].find("*/") {
+                    Some(end) => &rest[pos + 2 + end + 2..],
+                    None => "",
+                };
+            }
+            Some(pos) => {
+                stripped.push_str(&rest[..=pos]);
+                rest = &rest[pos + 1..];
+            }
+            None => {
+                stripped.push_str(rest);
+                return stripped;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scope_selectors() {
+        assert_eq!(
+            scope_css("h1, p > a:not(.x, .y) { color: red }", "#s"),
+            "#s h1, #s p > a:not(.x, .y) { color: red }\n"
+        );
+    }
+
+    #[test]
+    fn scope_root_selectors() {
+        assert_eq!(
+            scope_css("body { margin: 0 } html body.dark p { color: white }", "#s"),
+            "#s { margin: 0 }\n#s.dark p { color: white }\n"
+        );
+        assert_eq!(scope_css("bodyguard { }", "#s"), "#s bodyguard { }\n");
+    }
+
+    #[test]
+    fn scope_at_rules() {
+        assert_eq!(
+            scope_css(
+                "@import 'a.css'; @media (max-width: 600px) { a { b: c } } @font-face { font-family: x }",
+                "#s"
+            ),
+            "@import 'a.css';\n@media (max-width: 600px) {\n#s a { b: c }\n}\n@font-face {\nfont-family: x\n}\n"
+        );
+    }
+
+    #[test]
+    fn scope_skips_comments_and_strings() {
+        assert_eq!(
+            scope_css(r#"a /* b, c { */ { content: "{,}" }"#, "#s"),
+            "#s a { content: \"{,}\" }\n"
+        );
+    }
+
+    #[test]
+    fn escape_attribute_value() {
+        assert_eq!(
+            attribute_selector("data-doc-path", "blog/post"),
+            r#"[data-doc-path="blog/post"]"#
+        );
+        assert_eq!(
+            attribute_selector("data-doc-path", "a\"b\\c\nd"),
+            r#"[data-doc-path="a\"b\\c\a d"]"#
+        );
+        assert_eq!(
+            scope_css("p { color: red }", &attribute_selector("x", "\"] a, b[x")),
+            "[x=\"\\\"] a, b[x\"] p { color: red }\n"
+        );
+    }
+
+    #[test]
+    fn rebase_relative_urls() {
+        let dir: Path = "_doc/blog/css".parse().unwrap();
+        assert_eq!(
+            rebase_urls(
+                r#"@font-face { src: url("../fonts/a.woff2?v=1") } p { background: URL(img/b.png) }"#,
+                &dir
+            ),
+            r#"@font-face { src: url("_doc/blog/fonts/a.woff2?v=1") } p { background: URL(_doc/blog/css/img/b.png) }"#
+        );
+        assert_eq!(
+            rebase_urls(
+                r#"@import 'base.css'; @import url( "x.css" ) screen;"#,
+                &dir
+            ),
+            r#"@import '_doc/blog/css/base.css'; @import url( "_doc/blog/css/x.css" ) screen;"#
+        );
+    }
+
+    #[test]
+    fn keep_absolute_urls() {
+        let dir: Path = "_doc/blog".parse().unwrap();
+        let css = r#"a { b: url(/x.png); c: url('https://e.com/y.png'); d: url(data:image/png;base64,AA==); e: url(#f) }"#;
+        assert_eq!(rebase_urls(css, &dir), css);
+    }
+
+    #[test]
+    fn skip_urls_in_strings() {
+        let dir: Path = "_doc/blog".parse().unwrap();
+        let css = r#"a::before { content: "url(x.png)" } b { c: myurl(y) }"#;
+        assert_eq!(rebase_urls(css, &dir), css);
+    }
+}
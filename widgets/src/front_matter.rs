@@ -0,0 +1,146 @@
+//! Metadata block at the top of text files.
+//!
+//! YAML front matter is enclosed in `---` lines, TOML front matter in `+++` lines.
+
+use semka_core::manifests::PageMeta;
+use serde::Deserialize;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    pub date: Option<String>,
+    pub tags: Vec<String>,
+    pub author: Option<String>,
+    pub draft: bool,
+    pub summary: Option<String>,
+}
+
+impl FrontMatter {
+    /// Splits `text` into parsed front matter and the rest of the text.
+    /// Front matter is `None` if the text has none.
+    pub fn split(text: &str) -> Result<(Option<Self>, &str), String> {
+        Self::parse(text).map_err(|err| format!("Invalid front matter: {}", err))
+    }
+
+    fn parse(text: &str) -> Result<(Option<Self>, &str), String> {
+        let (format, head, body) = match split_block(text) {
+            Some(split) => split,
+            None => return Ok((None, text)),
+        };
+        let front_matter = match format {
+            // Markdown thematic breaks and setext headings also start with `---`,
+            // blocks that are not YAML mappings are left in the text.
+            Format::Yaml => match serde_yaml::from_str(head) {
+                Ok(serde_yaml::Value::Null) => Self::default(),
+                Ok(value @ serde_yaml::Value::Mapping(_)) => {
+                    serde_yaml::from_value(value).map_err(|err| err.to_string())?
+                }
+                _ => return Ok((None, text)),
+            },
+            Format::Toml => {
+                let mut value: toml::Value = toml::from_str(head).map_err(|err| err.to_string())?;
+                // TOML dates are their own type, they are kept as written.
+                let date = match value.get("date") {
+                    Some(toml::Value::Datetime(datetime)) => Some(datetime.to_string()),
+                    _ => None,
+                };
+                if let (Some(date), Some(table)) = (date, value.as_table_mut()) {
+                    table.insert("date".to_string(), toml::Value::String(date));
+                }
+                value.try_into().map_err(|err| err.to_string())?
+            }
+        };
+        Ok((Some(front_matter), body))
+    }
+
+    pub fn into_meta(self) -> PageMeta {
+        PageMeta {
+            title: self.title,
+            description: self.summary,
+            author: self.author,
+            date: self.date,
+            tags: self.tags,
+            draft: self.draft,
+            ..PageMeta::default()
+        }
+    }
+}
+
+enum Format {
+    Yaml,
+    Toml,
+}
+
+/// Number of lines front matter takes before the rest of the text.
+pub fn line_count(text: &str, body: &str) -> usize {
+    text[..text.len() - body.len()].matches('\n').count()
+}
+
+fn split_block(text: &str) -> Option<(Format, &str, &str)> {
+    let line_end = |start: usize| {
+        text[start..]
+            .find('\n')
+            .map_or(text.len(), |pos| start + pos + 1)
+    };
+    let head_start = line_end(0);
+    let (format, delimiter) = match text[..head_start].trim_end() {
+        "---" => (Format::Yaml, "---"),
+        "+++" => (Format::Toml, "+++"),
+        _ => return None,
+    };
+    let mut offset = head_start;
+    while offset < text.len() {
+        let end = line_end(offset);
+        if text[offset..end].trim_end() == delimiter {
+            return Some((format, &text[head_start..offset], &text[end..]));
+        }
+        offset = end;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_yaml() {
+        let text = "---\ntitle: Hello\ntags: [a, b]\n---\nBody\n";
+        let (front_matter, body) = FrontMatter::split(text).unwrap();
+        let front_matter = front_matter.unwrap();
+        assert_eq!(front_matter.title.as_deref(), Some("Hello"));
+        assert_eq!(front_matter.tags, vec!["a", "b"]);
+        assert_eq!(body, "Body\n");
+        assert_eq!(line_count(text, body), 4);
+    }
+
+    #[test]
+    fn split_toml() {
+        let text = "+++\ntitle = \"Hello\"\ndate = 2020-07-01\n+++\nBody\n";
+        let (front_matter, body) = FrontMatter::split(text).unwrap();
+        let front_matter = front_matter.unwrap();
+        assert_eq!(front_matter.title.as_deref(), Some("Hello"));
+        assert_eq!(front_matter.date.as_deref(), Some("2020-07-01"));
+        assert_eq!(body, "Body\n");
+    }
+
+    #[test]
+    fn keep_markdown_breaks() {
+        let texts = [
+            "---\nSome text\n---\nMore text\n",
+            "---\n- item\n---\n",
+            "---\n*Emphasis* text\n---\n",
+        ];
+        for text in texts.iter() {
+            let (front_matter, body) = FrontMatter::split(text).unwrap();
+            assert!(front_matter.is_none());
+            assert_eq!(body, *text);
+        }
+    }
+
+    #[test]
+    fn reject_invalid_fields() {
+        assert!(FrontMatter::split("---\ndraft: maybe\n---\n").is_err());
+    }
+}
@@ -1,8 +1,17 @@
 // (Lines like the one below ignore selected Clippy rules
 //  - it's useful when you want to check your code with `cargo make verify`
 // but some rules are too "annoying" or are not applicable for your case.)
-#![allow(clippy::wildcard_imports)]
+#![allow(
+    clippy::wildcard_imports,
+    // Widget constructors return `Box<dyn Widget>`.
+    clippy::new_ret_no_self,
+    clippy::new_without_default,
+    clippy::redundant_static_lifetimes,
+    clippy::enum_variant_names
+)]
 
+mod css;
+mod front_matter;
 mod markdown;
 mod stylesheet;
 
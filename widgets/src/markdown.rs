@@ -1,23 +1,52 @@
+use crate::front_matter::{self, FrontMatter};
+use futures::future::{FutureExt, LocalBoxFuture};
 use seed::{prelude::*, *};
+use semka_core::check::{Location, Reference};
+use semka_core::html;
 use semka_core::prelude::*;
-use std::collections::BTreeSet;
+use semka_core::storage;
+use serde::Deserialize;
+use std::rc::Rc;
 
 const WIDGET_NAME: &'static str = "semka-0.1-markdown";
 const WIDGET_CLASSES: &'static [&'static str] = &[WIDGET_NAME, "markdown"];
 const CAN_HANDLE: &'static [&'static str] = &[WIDGET_NAME];
 const TEXT_FILE: &str = "text.md";
 
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct Params {
+    file: Path,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            file: TEXT_FILE.parse().unwrap(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Markdown {
     doc_path: Path,
-    text: Option<String>,
+    file: Path,
+    // Takes precedence over front matter of the file.
+    manifest_meta: PageMeta,
+    // Parsed once when the text arrives and cloned on every view.
+    nodes: Option<Vec<Node<WidgetMsg>>>,
+    // Positions of include images in `nodes`, replaced with included documents on view.
+    includes: Vec<(Vec<usize>, Path)>,
 }
 
 impl Markdown {
-    pub fn new() -> Box<dyn Widget> {
+    pub fn new(file: Path, manifest_meta: PageMeta) -> Box<dyn Widget> {
         Box::new(Self {
             doc_path: Path::new(),
-            text: None,
+            file,
+            manifest_meta,
+            nodes: None,
+            includes: vec![],
         })
     }
 }
@@ -25,32 +54,43 @@ impl Markdown {
 impl Widget for Markdown {
     fn init(&mut self, doc_path: &Path, _ctx: &Context) -> Result<Option<WidgetOrders>, Error> {
         self.doc_path = doc_path.clone();
-        Ok(Some(WidgetOrders::new().fetch_text(TEXT_FILE.parse()?)))
+        Ok(Some(WidgetOrders::new().fetch_text(self.file.clone())))
     }
     fn update(&mut self, msg: WidgetMsg, _ctx: &Context) -> Result<Option<WidgetOrders>, Error> {
         assert!(!self.doc_path.is_empty(), "doc_path is empty");
         match msg {
-            WidgetMsg::FetchTextResult(_fpath, Ok(text)) => {
-                let deps = div![md!(&text)].fold(|node, children_deps: Vec<BTreeSet<Path>>| {
-                    include_path(&node)
-                        .into_iter()
-                        .chain(children_deps.into_iter().map(|c| c.into_iter()).flatten())
-                        .collect()
-                });
-                self.text.replace(text);
-                Ok(Some(WidgetOrders::new().update_deps(deps)))
+            WidgetMsg::FetchTextResult(fpath, Ok(text)) if fpath == self.file => {
+                let (front_matter, body) =
+                    FrontMatter::split(&text).map_err(|err| WidgetError::new(WIDGET_NAME, err))?;
+                let nodes = html::markdown(body);
+                self.includes.clear();
+                find_includes(&nodes, &mut vec![], &mut self.includes);
+                self.nodes.replace(nodes);
+                let deps = self.includes.iter().map(|(_, path)| path.clone()).collect();
+                let orders = WidgetOrders::new().update_deps(deps);
+                Ok(Some(match front_matter {
+                    Some(front_matter) => {
+                        orders.update_meta(self.manifest_meta.clone().or(&front_matter.into_meta()))
+                    }
+                    None => orders,
+                }))
             }
-            WidgetMsg::FetchTextResult(_fpath, Err(err)) => Err(err.into()),
+            WidgetMsg::FetchTextResult(fpath, Err(err)) if fpath == self.file => Err(err.into()),
             _ => Ok(None),
         }
     }
     fn view(&self, dependencies: Dependencies, _ctx: &Context) -> Node<WidgetMsg> {
         assert!(!self.doc_path.is_empty(), "doc_path is empty");
-        div![match &self.text {
-            Some(text) => md!(text)
-                .into_iter()
-                .map(|node| node.deep_map(|node| resolve_include(node, dependencies)))
-                .collect(),
+        div![match &self.nodes {
+            Some(nodes) => {
+                let mut nodes = nodes.clone();
+                for (position, doc_path) in &self.includes {
+                    if let Some(node) = node_at(&mut nodes, position) {
+                        *node = dependencies.view(doc_path);
+                    }
+                }
+                nodes
+            }
             None => vec![show_spinner()],
         }]
     }
@@ -76,33 +116,107 @@ impl WidgetFactory for MarkdownFactory {
     fn can_handle(&self) -> &'static [&'static str] {
         CAN_HANDLE
     }
-    fn create(&self, _: Path, _: DocManifest) -> Result<Box<dyn Widget>, WidgetError> {
-        Ok(Markdown::new())
+    fn create(
+        &self,
+        _: Path,
+        _: Path,
+        manifest: DocManifest,
+    ) -> Result<Box<dyn Widget>, WidgetError> {
+        let params: Params = manifest.parse_params()?;
+        Ok(Markdown::new(params.file, manifest.meta))
+    }
+    fn static_dependencies(
+        &self,
+        document: &Path,
+        _sub_path: &Path,
+        manifest: &DocManifest,
+        storage: Rc<dyn Storage>,
+    ) -> LocalBoxFuture<'static, Result<Vec<Reference>, WidgetError>> {
+        let widget = manifest.widget.clone();
+        let file = manifest.parse_params::<Params>().and_then(|params| {
+            doc_file_path(document, &params.file).map_err(|err| WidgetError::new(&widget, err))
+        });
+        async move {
+            let file = file?;
+            let text = storage::read_text(&*storage, &file)
+                .await
+                .map_err(|err| WidgetError::new(&widget, err))?;
+            let (_, body) =
+                FrontMatter::split(&text).map_err(|err| WidgetError::new(&widget, err))?;
+            let skipped = front_matter::line_count(&text, body);
+            Ok(includes(body)
+                .into_iter()
+                .map(|(target, line)| {
+                    Reference::new(target, Location::new(file.clone()).line(skipped + line))
+                })
+                .collect())
+        }
+        .boxed_local()
     }
 }
 
+/// Include targets of the Markdown text with their line numbers.
+/// Same as `include_path` but works without DOM.
+fn includes(text: &str) -> Vec<(Path, usize)> {
+    use pulldown_cmark::{Event, Options, Parser, Tag};
+    Parser::new_ext(text, Options::all())
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::Image(_, url, _)) => include_target(&url)
+                .map(|path| (path, text[..range.start].matches('\n').count() + 1)),
+            _ => None,
+        })
+        .collect()
+}
+
 fn include_path(node: &Node<WidgetMsg>) -> Option<Path> {
     match node {
         Node::Element(el) => Some(el),
         _ => None,
     }
     .filter(|el| el.tag == Tag::Img)
-    .map(|el| el.attrs.vals.get(&At::Src))
-    .flatten()
-    .map(|at| match at {
+    .and_then(|el| el.attrs.vals.get(&At::Src))
+    .and_then(|at| match at {
         AtValue::Some(src) => Some(src),
         _ => None,
     })
-    .flatten()
-    .filter(|url| !is_url_absolute(url))
-    .map(|url| url.parse::<Path>().ok())
-    .flatten()
+    .and_then(|url| include_target(url))
+}
+
+// Include targets with their positions as child indices starting from `nodes`.
+fn find_includes(
+    nodes: &[Node<WidgetMsg>],
+    position: &mut Vec<usize>,
+    includes: &mut Vec<(Vec<usize>, Path)>,
+) {
+    for (idx, node) in nodes.iter().enumerate() {
+        position.push(idx);
+        match (include_path(node), node) {
+            (Some(path), _) => includes.push((position.clone(), path)),
+            (None, Node::Element(el)) => find_includes(&el.children, position, includes),
+            _ => (),
+        }
+        position.pop();
+    }
 }
 
-fn resolve_include(node: Node<WidgetMsg>, dependencies: Dependencies) -> Node<WidgetMsg> {
-    if let Some(doc_path) = include_path(&node) {
-        dependencies.view(&doc_path)
-    } else {
-        node
+fn node_at<'a>(
+    nodes: &'a mut [Node<WidgetMsg>],
+    position: &[usize],
+) -> Option<&'a mut Node<WidgetMsg>> {
+    let (idx, rest) = position.split_first()?;
+    let node = nodes.get_mut(*idx)?;
+    if rest.is_empty() {
+        return Some(node);
     }
+    match node {
+        Node::Element(el) => node_at(&mut el.children, rest),
+        _ => None,
+    }
+}
+
+fn include_target(url: &str) -> Option<Path> {
+    Some(url)
+        .filter(|url| !is_url_absolute(url))
+        .and_then(|url| url.parse::<Path>().ok())
 }
@@ -1,20 +1,55 @@
+use crate::css::{attribute_selector, rebase_urls, scope_css};
+use futures::future::{self, FutureExt, LocalBoxFuture};
 use seed::{prelude::*, *};
+use semka_core::check::{Location, Reference};
 use semka_core::prelude::*;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
 
 const WIDGET_NAME: &'static str = "semka-0.1-stylesheet";
 const WIDGET_CLASSES: &'static [&'static str] = &[WIDGET_NAME, "stylesheet"];
 const CAN_HANDLE: &'static [&'static str] = &[WIDGET_NAME];
 const CSS_FILE: &str = "style.css";
 
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct Params {
+    files: Vec<Path>,
+    /// Applies rules only inside of the included document.
+    scoped: bool,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            files: vec![CSS_FILE.parse().unwrap()],
+            scoped: false,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Stylesheet {
     doc_path: Path,
+    document: Path,
+    // Path of the wrapped page.
+    sub_path: Path,
+    files: Vec<Path>,
+    scoped: bool,
+    // Fetched text of scoped files.
+    css: BTreeMap<Path, String>,
 }
 
 impl Stylesheet {
-    pub fn new() -> Box<dyn Widget> {
+    pub fn new(document: Path, sub_path: Path, files: Vec<Path>, scoped: bool) -> Box<dyn Widget> {
         Box::new(Self {
             doc_path: Path::new(),
+            document,
+            sub_path,
+            files,
+            scoped,
+            css: BTreeMap::new(),
         })
     }
 }
@@ -22,22 +57,60 @@ impl Stylesheet {
 impl Widget for Stylesheet {
     fn init(&mut self, doc_path: &Path, _ctx: &Context) -> Result<Option<WidgetOrders>, Error> {
         self.doc_path = doc_path.clone();
-        Ok(Some(WidgetOrders::new().update_deps(
-            vec![self.doc_path.tail()].into_iter().collect(),
-        )))
+        self.css.clear();
+        let orders =
+            WidgetOrders::new().update_deps(vec![self.sub_path.clone()].into_iter().collect());
+        if self.scoped {
+            return Ok(Some(
+                self.files
+                    .iter()
+                    .fold(orders, |orders, file| orders.fetch_text(file.clone())),
+            ));
+        }
+        let links = self
+            .files
+            .iter()
+            .map(|file| {
+                doc_file_path(&self.document, file)
+                    .map(|file_path| HeadElement::stylesheet(file_path.to_string()))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Some(orders.update_head(links)))
+    }
+    fn update(&mut self, msg: WidgetMsg, _ctx: &Context) -> Result<Option<WidgetOrders>, Error> {
+        match msg {
+            WidgetMsg::FetchTextResult(file, Ok(css)) if self.files.contains(&file) => {
+                self.css.insert(file, css);
+                if self.css.len() < self.files.len() {
+                    return Ok(None);
+                }
+                // Files are joined in order they are listed to keep the cascade.
+                let scope = attribute_selector(DOC_PATH_ATTR, &self.doc_path.to_string());
+                let css: Vec<String> = self
+                    .files
+                    .iter()
+                    .filter_map(|file| {
+                        let css = self.css.get(file)?;
+                        // Relative URLs of the file are resolved against the site root in the page.
+                        let file_path = doc_file_path(&self.document, file).ok()?;
+                        let dir = file_path.iter().take(file_path.len() - 1).collect();
+                        Some(scope_css(&rebase_urls(css, &dir), &scope))
+                    })
+                    .collect();
+                let style = HeadElement::Style(css.join("\n"));
+                Ok(Some(
+                    WidgetOrders::new().update_head(vec![style].into_iter().collect()),
+                ))
+            }
+            WidgetMsg::FetchTextResult(file, Err(err)) if self.files.contains(&file) => {
+                Err(err.into())
+            }
+            _ => Ok(None),
+        }
     }
     fn view(&self, dependencies: Dependencies, _ctx: &Context) -> Node<WidgetMsg> {
         assert!(!self.doc_path.is_empty(), "doc_path is empty");
-        div![
-            raw!(format!(
-                r#"<link rel="stylesheet" href="{}/{}/{}"/>"#,
-                DOC_DIR,
-                self.doc_path.head(),
-                CSS_FILE
-            )
-            .as_str()),
-            dependencies.view(&self.doc_path.tail())
-        ]
+        div![dependencies.view(&self.sub_path)]
     }
 
     fn widget_name(&self) -> &'static str {
@@ -61,7 +134,38 @@ impl WidgetFactory for StylesheetFactory {
     fn can_handle(&self) -> &'static [&'static str] {
         CAN_HANDLE
     }
-    fn create(&self, _: Path, _: DocManifest) -> Result<Box<dyn Widget>, WidgetError> {
-        Ok(Stylesheet::new())
+    fn create(
+        &self,
+        document: Path,
+        sub_path: Path,
+        manifest: DocManifest,
+    ) -> Result<Box<dyn Widget>, WidgetError> {
+        let mut params: Params = manifest.parse_params()?;
+        // Every file is fetched once, the first occurrence keeps its place in the cascade.
+        let mut seen = BTreeSet::new();
+        params.files.retain(|file| seen.insert(file.clone()));
+        Ok(Stylesheet::new(
+            document,
+            sub_path,
+            params.files,
+            params.scoped,
+        ))
+    }
+    fn static_dependencies(
+        &self,
+        document: &Path,
+        sub_path: &Path,
+        _manifest: &DocManifest,
+        _storage: Rc<dyn Storage>,
+    ) -> LocalBoxFuture<'static, Result<Vec<Reference>, WidgetError>> {
+        let manifest_file = Path::new()
+            .add(DOC_DIR)
+            .join(document)
+            .add(DOC_MANIFEST_FILE);
+        future::ok(vec![Reference::new(
+            sub_path.clone(),
+            Location::new(manifest_file),
+        )])
+        .boxed_local()
     }
 }